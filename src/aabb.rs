@@ -0,0 +1,121 @@
+use crate::interval::{Expand, Interval, Size};
+use crate::point3::Point3;
+use crate::vec3::Vec3;
+
+pub trait BoundingBox {
+    fn bounding_box(&self) -> Aabb;
+}
+
+/// Axis-aligned bounding box, stored as one interval per axis.
+pub struct Aabb {
+    pub x: Interval,
+    pub y: Interval,
+    pub z: Interval,
+}
+
+impl Aabb {
+    pub const EMPTY: Aabb = Aabb { x: Interval::EMPTY, y: Interval::EMPTY, z: Interval::EMPTY };
+    /// Box of objects without finite extent, such as infinite planes.
+    pub const UNIVERSE: Aabb = Aabb { x: Interval::UNIVERSE, y: Interval::UNIVERSE, z: Interval::UNIVERSE };
+
+    pub fn new(x: Interval, y: Interval, z: Interval) -> Self {
+        let mut bbox = Self { x, y, z };
+        bbox.pad_to_minimums();
+        bbox
+    }
+
+    /// Box spanned by two corner points, given in any order.
+    pub fn from_points(a: Point3, b: Point3) -> Self {
+        Self::new(
+            Interval::new(a.x.min(b.x), a.x.max(b.x)),
+            Interval::new(a.y.min(b.y), a.y.max(b.y)),
+            Interval::new(a.z.min(b.z), a.z.max(b.z)),
+        )
+    }
+
+    pub fn enclosing(box0: Aabb, box1: Aabb) -> Self {
+        Self {
+            x: Interval::enclosing(box0.x, box1.x),
+            y: Interval::enclosing(box0.y, box1.y),
+            z: Interval::enclosing(box0.z, box1.z),
+        }
+    }
+
+    /// Whether every side of the box is finite.
+    pub fn is_bounded(&self) -> bool {
+        [self.x, self.y, self.z].iter().all(|i| i.min.is_finite() && i.max.is_finite())
+    }
+
+    pub fn axis_interval(&self, n: usize) -> Interval {
+        match n {
+            1 => self.y,
+            2 => self.z,
+            _ => self.x,
+        }
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        if self.x.size() > self.y.size() {
+            if self.x.size() > self.z.size() { 0 } else { 2 }
+        } else if self.y.size() > self.z.size() {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        Point3::new(
+            0.5 * (self.x.min + self.x.max),
+            0.5 * (self.y.min + self.y.max),
+            0.5 * (self.z.min + self.z.max),
+        )
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let dx = self.x.size().max(0.0);
+        let dy = self.y.size().max(0.0);
+        let dz = self.z.size().max(0.0);
+        2.0 * (dx * dy + dy * dz + dz * dx)
+    }
+
+    /// Slab test against a ray given by its origin and per-axis reciprocal direction.
+    /// The reciprocal is computed once per ray by the caller so BVH traversal
+    /// doesn't divide at every node.
+    pub fn hit(&self, orig: Point3, inv_dir: Vec3, ray_t: Interval) -> bool {
+        let mut t_min = ray_t.min;
+        let mut t_max = ray_t.max;
+
+        for axis in 0..3 {
+            let ax = self.axis_interval(axis);
+            let t0 = (ax.min - orig[axis]) * inv_dir[axis];
+            let t1 = (ax.max - orig[axis]) * inv_dir[axis];
+            let (t0, t1) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+
+            if t_max <= t_min {
+                return false;
+            }
+        }
+        true
+    }
+
+    // Flat primitives produce zero-thickness boxes, which the slab test misses
+    // for rays travelling inside the plane. Give every axis a little depth.
+    fn pad_to_minimums(&mut self) {
+        let delta = 0.0001;
+        if self.x.size() < delta { self.x = self.x.expand(delta); }
+        if self.y.size() < delta { self.y = self.y.expand(delta); }
+        if self.z.size() < delta { self.z = self.z.expand(delta); }
+    }
+}
+
+impl Copy for Aabb {}
+
+impl Clone for Aabb {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
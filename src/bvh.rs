@@ -0,0 +1,340 @@
+use crate::aabb::{Aabb, BoundingBox};
+use crate::interval::Interval;
+use crate::point3::Point3;
+use crate::ray::{Direction, Origin, Ray};
+use crate::sphere::{Hit, HitRecord, HittableList, HittableObject};
+use crate::vec3::Vec3;
+
+// Relative costs used by the surface area heuristic.
+const TRAVERSAL_COST: f32 = 1.0;
+const INTERSECT_COST: f32 = 1.0;
+const SAH_BUCKETS: usize = 12;
+const MAX_LEAF_SIZE: usize = 4;
+// Past this depth only median splits are made, which bounds the total depth
+// (and so the traversal stack) even for pathological inputs.
+const MAX_SAH_DEPTH: usize = 64;
+const STACK_SIZE: usize = 128;
+
+/// Bounding volume hierarchy over a set of objects, built with a binned
+/// surface area heuristic. Nodes are stored depth-first in a flat array: the
+/// left child of an interior node always directly follows it, and `offset`
+/// holds the index of the right child. For leaves, `offset` is the first of
+/// `count` consecutive objects.
+///
+/// Objects without a finite bounding box, like infinite planes, can't be
+/// placed in the tree and are kept aside in `unbounded`, tested on every ray.
+#[derive(Clone)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    objects: Vec<HittableObject>,
+    unbounded: Vec<HittableObject>,
+}
+
+#[derive(Clone)]
+struct BvhNode {
+    bbox: Aabb,
+    offset: usize,
+    count: usize,
+    axis: usize,
+}
+
+struct BuildPrimitive {
+    index: usize,
+    bbox: Aabb,
+    centroid: Point3,
+}
+
+struct Bucket {
+    count: usize,
+    bbox: Aabb,
+}
+
+impl Bvh {
+    pub fn new(list: HittableList) -> Self {
+        let (objects, unbounded): (Vec<_>, Vec<_>) =
+            list.objects.into_iter().partition(|object| object.bounding_box().is_bounded());
+        let mut primitives: Vec<BuildPrimitive> = objects
+            .iter()
+            .enumerate()
+            .map(|(index, object)| {
+                let bbox = object.bounding_box();
+                BuildPrimitive { index, bbox, centroid: bbox.centroid() }
+            })
+            .collect();
+
+        let mut nodes = Vec::with_capacity(2 * objects.len());
+        if !primitives.is_empty() {
+            build(&mut nodes, &mut primitives, 0, 0);
+        }
+
+        // Reorder objects so every leaf references a contiguous range.
+        let mut slots: Vec<Option<HittableObject>> = objects.into_iter().map(Some).collect();
+        let objects = primitives
+            .iter()
+            .map(|p| slots[p.index].take().unwrap())
+            .collect();
+
+        Bvh { nodes, objects, unbounded }
+    }
+}
+
+fn build(nodes: &mut Vec<BvhNode>, primitives: &mut [BuildPrimitive], first: usize, depth: usize) -> usize {
+    let bbox = primitives.iter().fold(Aabb::EMPTY, |acc, p| Aabb::enclosing(acc, p.bbox));
+    let node_index = nodes.len();
+    nodes.push(BvhNode { bbox, offset: first, count: primitives.len(), axis: 0 });
+
+    let n = primitives.len();
+    if n == 1 {
+        return node_index;
+    }
+
+    let centroid_bounds = primitives
+        .iter()
+        .fold(Aabb::EMPTY, |acc, p| Aabb::enclosing(acc, Aabb::from_points(p.centroid, p.centroid)));
+
+    let split = if depth < MAX_SAH_DEPTH { split_sah(primitives, &bbox, &centroid_bounds) } else { None };
+    let mid = match split {
+        Some((axis, mid)) => {
+            nodes[node_index].axis = axis;
+            mid
+        }
+        None if n <= MAX_LEAF_SIZE => return node_index,
+        None => {
+            // Splitting doesn't pay off but the leaf would be too large, most
+            // likely because all centroids coincide, or the tree got too deep.
+            // Fall back to a median split.
+            let axis = centroid_bounds.longest_axis();
+            primitives.sort_by(|a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+            nodes[node_index].axis = axis;
+            n / 2
+        }
+    };
+
+    let (left, right) = primitives.split_at_mut(mid);
+    build(nodes, left, first, depth + 1);
+    let right_index = build(nodes, right, first + mid, depth + 1);
+
+    nodes[node_index].offset = right_index;
+    nodes[node_index].count = 0;
+    node_index
+}
+
+/// Picks the cheapest bucket boundary over all three axes and partitions the
+/// primitives around it. Returns `None` when a leaf is cheaper than any split.
+fn split_sah(primitives: &mut [BuildPrimitive], bbox: &Aabb, centroid_bounds: &Aabb) -> Option<(usize, usize)> {
+    let n = primitives.len();
+    let parent_area = bbox.surface_area();
+    let mut best: Option<(f32, usize, usize)> = None;
+
+    for axis in 0..3 {
+        let extent = centroid_bounds.axis_interval(axis);
+        if extent.max <= extent.min {
+            continue;
+        }
+
+        let mut buckets: Vec<Bucket> = (0..SAH_BUCKETS).map(|_| Bucket { count: 0, bbox: Aabb::EMPTY }).collect();
+        for p in primitives.iter() {
+            let b = bucket_index(p.centroid[axis], extent);
+            buckets[b].count += 1;
+            buckets[b].bbox = Aabb::enclosing(buckets[b].bbox, p.bbox);
+        }
+
+        // Sweep from the right to collect suffix areas, then from the left.
+        let mut right_area = [0.0; SAH_BUCKETS];
+        let mut right_count = [0; SAH_BUCKETS];
+        let mut acc_box = Aabb::EMPTY;
+        let mut acc_count = 0;
+        for i in (1..SAH_BUCKETS).rev() {
+            acc_box = Aabb::enclosing(acc_box, buckets[i].bbox);
+            acc_count += buckets[i].count;
+            right_area[i] = acc_box.surface_area();
+            right_count[i] = acc_count;
+        }
+
+        let mut acc_box = Aabb::EMPTY;
+        let mut acc_count = 0;
+        for split in 1..SAH_BUCKETS {
+            acc_box = Aabb::enclosing(acc_box, buckets[split - 1].bbox);
+            acc_count += buckets[split - 1].count;
+            if acc_count == 0 || right_count[split] == 0 {
+                continue;
+            }
+            let cost = TRAVERSAL_COST
+                + INTERSECT_COST
+                    * (acc_box.surface_area() * acc_count as f32 + right_area[split] * right_count[split] as f32)
+                    / parent_area;
+            if best.is_none_or(|(c, _, _)| cost < c) {
+                best = Some((cost, axis, split));
+            }
+        }
+    }
+
+    let (cost, axis, split) = best?;
+    let leaf_cost = INTERSECT_COST * n as f32;
+    if n <= MAX_LEAF_SIZE && cost >= leaf_cost {
+        return None;
+    }
+
+    let extent = centroid_bounds.axis_interval(axis);
+    let mut mid = 0;
+    for i in 0..n {
+        if bucket_index(primitives[i].centroid[axis], extent) < split {
+            primitives.swap(i, mid);
+            mid += 1;
+        }
+    }
+    Some((axis, mid))
+}
+
+fn bucket_index(c: f32, extent: Interval) -> usize {
+    let b = (SAH_BUCKETS as f32 * (c - extent.min) / (extent.max - extent.min)) as usize;
+    b.min(SAH_BUCKETS - 1)
+}
+
+impl Hit for Bvh {
+    fn hit<'a>(&'a self, r: Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        let mut hit_anything = false;
+        let mut closest_so_far = ray_t.max;
+        for object in &self.unbounded {
+            if object.hit(r, Interval::new(ray_t.min, closest_so_far), rec) {
+                hit_anything = true;
+                closest_so_far = rec.t;
+            }
+        }
+        if self.nodes.is_empty() {
+            return hit_anything;
+        }
+
+        let orig = r.origin();
+        let dir = r.direction();
+        let inv_dir = Vec3::new(1.0 / dir.x, 1.0 / dir.y, 1.0 / dir.z);
+        let dir_is_neg = [dir.x < 0.0, dir.y < 0.0, dir.z < 0.0];
+
+        let mut stack = [0usize; STACK_SIZE];
+        let mut stack_len = 0;
+        let mut current = 0;
+
+        loop {
+            let node = &self.nodes[current];
+            if node.bbox.hit(orig, inv_dir, Interval::new(ray_t.min, closest_so_far)) {
+                if node.count > 0 {
+                    for object in &self.objects[node.offset..node.offset + node.count] {
+                        if object.hit(r, Interval::new(ray_t.min, closest_so_far), rec) {
+                            hit_anything = true;
+                            closest_so_far = rec.t;
+                        }
+                    }
+                } else {
+                    // Visit the child nearer to the ray origin first so the
+                    // far one is more likely to be culled by `closest_so_far`.
+                    let (near, far) = if dir_is_neg[node.axis] {
+                        (node.offset, current + 1)
+                    } else {
+                        (current + 1, node.offset)
+                    };
+                    stack[stack_len] = far;
+                    stack_len += 1;
+                    current = near;
+                    continue;
+                }
+            }
+            if stack_len == 0 {
+                break;
+            }
+            stack_len -= 1;
+            current = stack[stack_len];
+        }
+
+        hit_anything
+    }
+}
+
+impl BoundingBox for Bvh {
+    fn bounding_box(&self) -> Aabb {
+        if !self.unbounded.is_empty() {
+            return Aabb::UNIVERSE;
+        }
+        self.nodes.first().map_or(Aabb::EMPTY, |root| root.bbox)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::{DiffuseLight, Emitted, Material};
+    use crate::plane::Plane;
+    use crate::rtweekend::{random_double, INF};
+    use crate::sphere::{Add, New, Sphere};
+    use crate::texture::{SolidColor, Texture};
+    use crate::vec3::{random_unit_vector, Length};
+
+    /// A material that tells objects apart by the color it emits.
+    fn tagged(tag: usize) -> Material {
+        Material::DiffuseLight(DiffuseLight::from_texture(Texture::Solid(SolidColor { albedo: Color::new(tag as f32, 0.0, 0.0) })))
+    }
+
+    fn tag(rec: &HitRecord) -> f32 {
+        rec.mat.emitted(0.0, 0.0, rec.p).x
+    }
+
+    /// The book cover layout: a ground plane, 484 small spheres on a
+    /// jittered grid and three large ones.
+    fn book_cover() -> HittableList {
+        let mut world = HittableList::new();
+        world.add(HittableObject::Plane(Plane::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), tagged(0))));
+        for a in -11..11 {
+            for b in -11..11 {
+                let center = Point3::new(a as f32 + 0.9 * random_double(), 0.2, b as f32 + 0.9 * random_double());
+                let index = world.objects.len();
+                world.add(HittableObject::Sphere(Sphere { center, radius: 0.2, mat: tagged(index) }));
+            }
+        }
+        for x in [-4.0, 0.0, 4.0] {
+            let index = world.objects.len();
+            world.add(HittableObject::Sphere(Sphere { center: Point3::new(x, 1.0, 0.0), radius: 1.0, mat: tagged(index) }));
+        }
+        world
+    }
+
+    fn random_ray() -> Ray {
+        let origin = Point3::new(30.0 * random_double() - 15.0, 6.0 * random_double() - 1.0, 30.0 * random_double() - 15.0);
+        Ray::new(origin, random_unit_vector())
+    }
+
+    /// Casts random rays at `world` and checks that the BVH over it finds
+    /// exactly what testing every object does.
+    fn assert_matches_list(world: HittableList) {
+        let bvh = Bvh::new(world.clone());
+        let mut hits = 0;
+        for _ in 0..20_000 {
+            let r = random_ray();
+            let ray_t = Interval::new(0.001, INF);
+            let mut expected = HitRecord::new();
+            let mut actual = HitRecord::new();
+            let hit = world.hit(r, ray_t, &mut expected);
+            assert_eq!(bvh.hit(r, ray_t, &mut actual), hit);
+            if !hit {
+                continue;
+            }
+            hits += 1;
+            assert!((actual.t - expected.t).abs() <= 1e-5 * expected.t.max(1.0), "t {} vs {}", actual.t, expected.t);
+            assert!((actual.normal - expected.normal).length() < 1e-5);
+            assert_eq!(tag(&actual), tag(&expected));
+        }
+        assert!(hits > 1000);
+    }
+
+    #[test]
+    fn matches_list_on_book_cover() {
+        assert_matches_list(book_cover());
+    }
+
+    #[test]
+    fn matches_list_with_only_unbounded_objects() {
+        let mut world = HittableList::new();
+        world.add(HittableObject::Plane(Plane::new(Point3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), tagged(0))));
+        world.add(HittableObject::Plane(Plane::new(Point3::new(0.0, 3.0, 0.0), Vec3::new(1.0, -1.0, 0.0), tagged(1))));
+        assert_matches_list(world);
+    }
+}
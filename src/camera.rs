@@ -1,6 +1,41 @@
-use indicatif::ProgressBar;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Instant;
 
-use crate::{color::{self, Color}, interval::Interval, material::Scatter, point3::Point3, ray::{self, Direction, Ray}, rtweekend::{degrees_to_radians, random_double, INF}, sphere::{Hit, HitRecord, HittableList, New}, vec3::{cross, element_wise_mul, random_in_unit_disk, random_on_hemisphere, random_unit_vector, Length, Unit, Vec3}};
+use indicatif::{ProgressBar, ProgressStyle};
+
+use crate::{color::Color, environment::EnvironmentMap, film::Film, integrator::{Integrator, IntegratorKind, PathIntegrator, RenderContext}, light::Light, medium::MediumStack, point3::Point3, ray::{Direction, Ray}, rtweekend::{degrees_to_radians, random_double}, sky::Sky, spectrum::{Spectral, Wavelengths}, sphere::{HittableList, New}, vec3::{cross, random_in_unit_disk, Unit, Vec3}};
+
+/// What a ray sees when it escapes the scene.
+pub enum Background {
+    /// The white-to-blue sky blend from the book.
+    Gradient,
+    Solid(Color),
+    /// An HDR image all around the scene. It should also be among the
+    /// lights so that it gets sampled directly.
+    Environment(Arc<EnvironmentMap>),
+    /// Physically based daylight. Like the environment map it should also
+    /// be among the lights.
+    Sky(Arc<Sky>),
+}
+
+impl Background {
+    /// Radiance arriving along `r` from outside the scene.
+    pub fn value(&self, r: &Ray) -> Color {
+        match self {
+            Background::Gradient => {
+                let unit_direction = r.direction().unit();
+                let a = 0.5 * (unit_direction.y + 1.0);
+                Vec3::new(1.0, 1.0, 1.0) * (1.0 - a) + Vec3::new(0.5, 0.7, 1.0) * a
+            }
+            Background::Solid(color) => *color,
+            Background::Environment(map) => map.value(r.direction()),
+            Background::Sky(sky) => sky.value(r.direction()),
+        }
+    }
+}
 
 pub struct Camera {
     pub aspect_ratio: f32,
@@ -13,6 +48,19 @@ pub struct Camera {
     pub vup: Vec3,
     pub defocus_angle: f32,
     pub focus_dist: f32,
+    /// Rays are cast at times spread uniformly over `shutter_open..shutter_close`,
+    /// which blurs anything that moves during that interval.
+    pub shutter_open: f32,
+    pub shutter_close: f32,
+    pub background: Background,
+    pub integrator: IntegratorKind,
+    /// Trace paths at sampled wavelengths rather than in RGB, which is
+    /// slower and noisier but lets light depend on wavelength.
+    pub spectral: bool,
+    /// Number of worker threads used by `render`.
+    pub threads: usize,
+    /// Edge length in pixels of the square tiles handed out to workers.
+    pub tile_size: i32,
     pixel_samples_scale: f32,
     image_height: i32,
     center: Point3,
@@ -31,40 +79,41 @@ pub trait Initialize {
 }
 
 pub trait Render {
-    fn render(&mut self, world: &HittableList);
+    fn render(&mut self, world: &HittableList, lights: &[Light]) -> Film;
 }
 
 trait GetRay {
     fn get_ray(&self, i: i32, j: i32) -> Ray;
 }
 
-trait RayColor {
-    fn ray_color(&self, r: Ray, depth: i32, world: &HittableList) -> Vec3;
-}
-
 trait DefocusDiskSample {
     fn defocus_disk_sample(&self) -> Vec3;
 }
 
-impl RayColor for Camera {
-    fn ray_color(&self, r: Ray, depth: i32, world: &HittableList) -> Vec3 {
-        if (depth <= 0) {
-            return Vec3::new(0.0,0.0,0.0) as Color
-        }
-        let mut rec = HitRecord::new();
-        if world.hit(r, Interval::new(0.001, INF), &mut rec) {
-            let mut scattered = Ray::new(Vec3::new(0.0,0.0,0.0), Vec3::new(0.0,0.0,0.0));
-            let mut attenuation = Vec3::new(0.0,0.0,0.0);
-            if rec.mat.clone().scatter(&r, &mut rec, &mut attenuation, &mut scattered) {
-                // eprintln!("RayColor: attenuation = {:?}, scattered = {:?}", attenuation, scattered);
-                return element_wise_mul(attenuation, self.ray_color(scattered, depth-1, world))
-            }
-            return Color::new(0.0,0.0,0.0)
-        }
-        let unit_direction = r.direction().unit();
-        let a = 0.5 * (unit_direction.y + 1.0);
-        return Vec3::new(1.0, 1.0, 1.0) * (1.0 - a) + Vec3::new(0.5, 0.7, 1.0) * a;
-    }
+trait RenderTile {
+    fn render_tile(&self, tile: &Tile, ctx: &RenderContext, spectral: Option<&Spectral>) -> Vec<Color>;
+}
+
+/// A rectangle of pixels, `x0..x1` by `y0..y1`.
+struct Tile {
+    x0: i32,
+    y0: i32,
+    x1: i32,
+    y1: i32,
+}
+
+thread_local! {
+    static RAYS_TRACED: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Records one ray cast into the scene, for the rays/s statistic.
+pub(crate) fn count_ray() {
+    RAYS_TRACED.with(|n| n.set(n.get() + 1));
+}
+
+/// Returns and resets the number of rays the calling thread has cast.
+fn take_ray_count() -> u64 {
+    RAYS_TRACED.with(|n| n.replace(0))
 }
 
 impl Initialize for Camera {
@@ -101,30 +150,116 @@ impl Initialize for Camera {
 }
 
 impl Render for Camera {
-    fn render(&mut self, world: &HittableList) {
+    fn render(&mut self, world: &HittableList, lights: &[Light]) -> Film {
         self.initialize();
+        let cam: &Camera = self;
 
-        // Progress
+        let tile_size = cam.tile_size.max(1);
+        let mut tiles = Vec::new();
+        for y0 in (0..cam.image_height).step_by(tile_size as usize) {
+            for x0 in (0..cam.image_width).step_by(tile_size as usize) {
+                tiles.push(Tile {
+                    x0,
+                    y0,
+                    x1: (x0 + tile_size).min(cam.image_width),
+                    y1: (y0 + tile_size).min(cam.image_height),
+                });
+            }
+        }
 
-        let bar = ProgressBar::new(self.image_height as u64);
+        // Progress
 
-        println!("P3\n{} {}\n255", self.image_width, self.image_height);
+        let bar = ProgressBar::new(tiles.len() as u64);
+        bar.set_style(
+            ProgressStyle::with_template("{elapsed_precise} [{bar:40}] {pos}/{len} tiles, {msg}")
+                .unwrap()
+                .progress_chars("=> "),
+        );
 
         // Main Render Loop
+        //
+        // Workers pull the next unclaimed tile from a shared counter and send
+        // finished pixels back to this thread, which owns the framebuffer and
+        // the progress bar. The scene is only borrowed, and every worker draws
+        // from its own thread-local RNG.
+
+        // Punctual lights can't be sampled by density, so integrators handle
+        // them apart from the rest.
+        let (delta_lights, lights): (Vec<Light>, Vec<Light>) = lights.iter().cloned().partition(Light::is_delta);
+        let ctx = RenderContext { world, lights: &lights, delta_lights: &delta_lights, background: &cam.background, spectral: None, dispersed_wavelength: None, media: MediumStack::new() };
+        let spectral = cam.spectral.then(Spectral::new);
+
+        let mut film = Film::new(cam.image_width as usize, cam.image_height as usize);
+        let next_tile = AtomicUsize::new(0);
+        let start = Instant::now();
+        let mut total_rays: u64 = 0;
+
+        thread::scope(|scope| {
+            let (sender, receiver) = mpsc::channel();
+            for _ in 0..cam.threads.max(1) {
+                let sender = sender.clone();
+                let tiles = &tiles;
+                let next_tile = &next_tile;
+                let ctx = &ctx;
+                let spectral = spectral.as_ref();
+                scope.spawn(move || loop {
+                    let index = next_tile.fetch_add(1, Ordering::Relaxed);
+                    if index >= tiles.len() {
+                        break;
+                    }
+                    let pixels = cam.render_tile(&tiles[index], ctx, spectral);
+                    if sender.send((index, pixels, take_ray_count())).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(sender);
+
+            for (index, pixels, rays) in receiver {
+                let tile = &tiles[index];
+                let tile_width = (tile.x1 - tile.x0) as usize;
+                for (k, &pixel_color) in pixels.iter().enumerate() {
+                    film.set(tile.x0 as usize + k % tile_width, tile.y0 as usize + k / tile_width, pixel_color);
+                }
+
+                total_rays += rays;
+                let rays_per_second = total_rays as f64 / start.elapsed().as_secs_f64();
+                bar.set_message(format!("{:.2} Mrays/s", rays_per_second / 1e6));
+                bar.inc(1);
+            }
+        });
+
+        bar.finish();
+        eprintln!("\nDone!", );
 
-        for j in 0..self.image_height {
-            bar.inc(1);
-            for i in 0..self.image_width {
+        film
+    }
+}
+
+impl RenderTile for Camera {
+    /// With `spectral`, every sample traces its own set of wavelengths and
+    /// is turned back into RGB before being added up.
+    fn render_tile(&self, tile: &Tile, ctx: &RenderContext, spectral: Option<&Spectral>) -> Vec<Color> {
+        let mut pixels = Vec::with_capacity(((tile.x1 - tile.x0) * (tile.y1 - tile.y0)) as usize);
+        for j in tile.y0..tile.y1 {
+            for i in tile.x0..tile.x1 {
                 let mut pixel_color = Vec3::new(0.0, 0.0, 0.0);
                 for _ in 0..self.samples_per_pixel {
                     let r = self.get_ray(i,j);
-                    pixel_color = pixel_color + self.ray_color(r, self.max_depth, &world.clone());
+                    let sample = match spectral {
+                        Some(spectral) => {
+                            let wavelengths = Wavelengths::sample(random_double());
+                            let ctx = RenderContext { spectral: Some((spectral, wavelengths)), ..*ctx };
+                            spectral.to_rgb(self.integrator.ray_color(r, self.max_depth, &ctx), &wavelengths)
+                        }
+                        None => self.integrator.ray_color(r, self.max_depth, ctx),
+                    };
+                    pixel_color = pixel_color + sample;
                 }
-                color::write_color(pixel_color * self.pixel_samples_scale);
+                pixels.push(pixel_color * self.pixel_samples_scale);
             }
         }
-
-        eprintln!("\nDone!", );
+        pixels
     }
 }
 
@@ -152,6 +287,13 @@ impl New for Camera {
             defocus_disk_v: Vec3::new(0.0, 0.0, 0.0),
             defocus_angle: 0.0,
             focus_dist  : 10.0,
+            shutter_open: 0.0,
+            shutter_close: 1.0,
+            background: Background::Gradient,
+            integrator: IntegratorKind::Path(PathIntegrator::new()),
+            spectral: false,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            tile_size: 16,
         }
     }
 }
@@ -166,10 +308,12 @@ impl GetRay for Camera {
             self.defocus_disk_sample()
         };
         let ray_direction = pixel_sample - ray_origin;
+        let ray_time = self.shutter_open + random_double() * (self.shutter_close - self.shutter_open);
 
         Ray {
             orig: ray_origin,
             dir: ray_direction,
+            tm: ray_time,
         }
     }
 }
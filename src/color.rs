@@ -2,27 +2,44 @@ use crate::{interval::{self, Clamp}, vec3::Vec3};
 
 pub type Color = Vec3;
 
-fn linear_to_gamma(linear_component: f32) -> f32{
-    if linear_component > 0.0 {
-        return linear_component.sqrt()
-    }
-    return 0.0;
+/// Relative luminance of a linear Rec. 709 / sRGB color.
+pub fn luminance(color: Color) -> f32 {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
 }
 
-pub fn write_color(color: Color) {
-
-    let intensity: interval::Interval = interval::Interval::new(0.0, 0.999);
-
-    let r = linear_to_gamma(color.x);
-    let g = linear_to_gamma(color.y);
-    let b = linear_to_gamma(color.z);
+/// Converts CIE XYZ to linear Rec. 709 / sRGB (D65 white). Colors outside
+/// the sRGB gamut come out with negative components.
+pub fn xyz_to_linear_srgb(xyz: Vec3) -> Color {
+    Color::new(
+        3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z,
+        -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z,
+        0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z,
+    )
+}
 
-    let ir = (255.999 * intensity.clamp(r)) as i32;
-    let ig = (255.999 * intensity.clamp(g)) as i32;
-    let ib = (255.999 * intensity.clamp(b)) as i32;
+/// sRGB transfer function, applied to linear values in [0, 1].
+pub fn linear_to_srgb(linear_component: f32) -> f32 {
+    if linear_component <= 0.0 {
+        return 0.0
+    }
+    if linear_component <= 0.0031308 {
+        12.92 * linear_component
+    } else {
+        1.055 * linear_component.powf(1.0 / 2.4) - 0.055
+    }
+}
 
+/// Encodes a linear color as 8-bit sRGB, clamping out-of-range values.
+pub fn to_rgb8(color: Color) -> [u8; 3] {
+    let intensity: interval::Interval = interval::Interval::new(0.0, 0.999);
 
+    let r = linear_to_srgb(color.x);
+    let g = linear_to_srgb(color.y);
+    let b = linear_to_srgb(color.z);
 
-    println!("{} {} {}", ir, ig, ib);
+    [
+        (256.0 * intensity.clamp(r)) as u8,
+        (256.0 * intensity.clamp(g)) as u8,
+        (256.0 * intensity.clamp(b)) as u8,
+    ]
 }
-
@@ -0,0 +1,54 @@
+/// Wavelength, in nm, at which glass is usually given a single index of
+/// refraction: the helium d line.
+pub const D_LINE: f32 = 587.6;
+
+/// Index of refraction that changes with wavelength, which is what splits
+/// white light into a rainbow. Both models take the wavelength in µm, as
+/// their coefficients are usually quoted.
+#[derive(Clone, Copy)]
+pub enum Dispersion {
+    /// Cauchy's equation, `n = a + b / λ²`. Good enough for most glasses
+    /// over the visible range.
+    Cauchy { a: f32, b: f32 },
+    /// The Sellmeier equation, `n² = 1 + Σ b[i] λ² / (λ² - c[i])`, fitted
+    /// to measurements for many materials.
+    Sellmeier { b: [f32; 3], c: [f32; 3] },
+}
+
+/// Measured materials for `Dispersion::preset`.
+#[derive(Clone, Copy)]
+pub enum DispersionPreset {
+    /// Schott N-BK7, the most common optical glass.
+    Bk7,
+    FusedSilica,
+    Diamond,
+}
+
+impl Dispersion {
+    /// Sellmeier coefficients from Schott's data sheet for BK7, Malitson
+    /// (1965) for fused silica and Peter (1923) for diamond.
+    pub fn preset(material: DispersionPreset) -> Self {
+        match material {
+            DispersionPreset::Bk7 => Dispersion::Sellmeier {
+                b: [1.039_612, 0.231_792_34, 1.010_469_5],
+                c: [0.006_000_699, 0.020_017_914, 103.560_65],
+            },
+            DispersionPreset::FusedSilica => Dispersion::Sellmeier {
+                b: [0.696_166_3, 0.407_942_6, 0.897_479_4],
+                c: [0.004_679_148, 0.013_512_06, 97.934],
+            },
+            DispersionPreset::Diamond => Dispersion::Sellmeier { b: [0.3306, 4.3356, 0.0], c: [0.030_625, 0.011_236, 0.0] },
+        }
+    }
+
+    /// Index of refraction at `lambda` in nm.
+    pub fn ior(&self, lambda: f32) -> f32 {
+        let l2 = (lambda / 1000.0).powi(2);
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                (1.0 + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<f32>()).sqrt()
+            }
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use crate::color::{luminance, Color};
+use crate::image::Image;
+use crate::light::SampleLight;
+use crate::point3::Point3;
+use crate::rtweekend::{random_double, PI};
+use crate::transform::Transform;
+use crate::vec3::{Unit, Vec3};
+
+/// Equirectangular image surrounding the scene at infinity, lighting it
+/// from every direction. It uses the same (u, v) mapping as spheres, with
+/// the top row of the image straight up (+y).
+///
+/// Directions are importance sampled in proportion to the luminance of the
+/// pixel they land in, so small bright features such as the sun get most
+/// of the samples.
+pub struct EnvironmentMap {
+    image: Image,
+    intensity: f32,
+    to_world: Transform,
+    to_local: Transform,
+    /// Cumulative distribution over the rows, `height + 1` entries from 0 to 1.
+    marginal: Vec<f32>,
+    /// Cumulative distribution over the pixels within each row,
+    /// `width + 1` entries per row.
+    conditional: Vec<f32>,
+    /// Probability of sampling each pixel, row by row.
+    pixel_probability: Vec<f32>,
+}
+
+impl EnvironmentMap {
+    /// `rotation` turns the map about the y axis, in degrees; `intensity`
+    /// scales the radiance of every pixel. The image can't be empty, which
+    /// `Image::load` already ensures.
+    pub fn new(image: Image, rotation: f32, intensity: f32) -> Self {
+        let (width, height) = (image.width, image.height);
+        assert!(width > 0 && height > 0, "environment map from an empty {}x{} image", width, height);
+        let to_world = Transform::rotate(Vec3::new(0.0, 1.0, 0.0), rotation);
+        let to_local = to_world.inverse();
+
+        // Rows near the poles cover less solid angle, hence the sine. A black
+        // map falls back to sampling uniformly over the sphere.
+        let row_sine = |y: usize| (PI * (y as f32 + 0.5) / height as f32).sin();
+        let mut weights: Vec<f32> = (0..width * height)
+            .map(|i| luminance(image.pixel(i % width, i / width)).max(0.0) * row_sine(i / width))
+            .collect();
+        if weights.iter().all(|&w| w <= 0.0) {
+            weights = (0..width * height).map(|i| row_sine(i / width)).collect();
+        }
+        let total: f32 = weights.iter().sum();
+
+        let mut marginal = vec![0.0; height + 1];
+        let mut conditional = vec![0.0; (width + 1) * height];
+        for (y, row) in weights.chunks_exact(width).enumerate() {
+            let row_total: f32 = row.iter().sum();
+            marginal[y + 1] = marginal[y] + row_total / total;
+            let cdf = &mut conditional[y * (width + 1)..(y + 1) * (width + 1)];
+            for (x, &w) in row.iter().enumerate() {
+                // Rows that are never picked still get a valid, uniform CDF.
+                let w = if row_total > 0.0 { w / row_total } else { 1.0 / width as f32 };
+                cdf[x + 1] = cdf[x] + w;
+            }
+        }
+        let pixel_probability = weights.iter().map(|w| w / total).collect();
+
+        Self { image, intensity, to_world, to_local, marginal, conditional, pixel_probability }
+    }
+
+    /// Pixel seen along the local-space unit `direction`, and the sine of
+    /// the direction's angle from the y axis. The angle comes from `atan2`
+    /// rather than `acos`, which loses the sine entirely near the poles.
+    fn lookup(&self, direction: Vec3) -> (usize, usize, f32) {
+        let sin_theta = (direction.x * direction.x + direction.z * direction.z).sqrt();
+        let theta = sin_theta.atan2(-direction.y);
+        let phi = (-direction.z).atan2(direction.x) + PI;
+        let (u, v) = (phi / (2.0 * PI), theta / PI);
+        let x = ((u * self.image.width as f32) as usize).min(self.image.width - 1);
+        let y = (((1.0 - v) * self.image.height as f32) as usize).min(self.image.height - 1);
+        (x, y, sin_theta)
+    }
+
+    /// Radiance arriving from `direction`.
+    pub fn value(&self, direction: Vec3) -> Color {
+        let (x, y, _) = self.lookup(self.to_local.vector(direction).unit());
+        self.image.pixel(x, y) * self.intensity
+    }
+
+    /// Solid-angle density of `sample` producing `direction`.
+    pub fn pdf(&self, direction: Vec3) -> f32 {
+        let (x, y, sin_theta) = self.lookup(self.to_local.vector(direction).unit());
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        // The density is uniform over each pixel's rectangle in (u, v), and
+        // the equirectangular map stretches it by 2π² sin(theta).
+        let pdf_uv = self.pixel_probability[y * self.image.width + x] * (self.image.width * self.image.height) as f32;
+        pdf_uv / (2.0 * PI * PI * sin_theta)
+    }
+
+    /// A random unit direction, drawn in proportion to the map's luminance.
+    pub fn sample(&self) -> Vec3 {
+        let (y, ty) = sample_cdf(&self.marginal, random_double());
+        let width = self.image.width;
+        let (x, tx) = sample_cdf(&self.conditional[y * (width + 1)..(y + 1) * (width + 1)], random_double());
+
+        let u = (x as f32 + tx) / width as f32;
+        let v = 1.0 - (y as f32 + ty) / self.image.height as f32;
+        self.to_world.vector(equirect_direction(u, v))
+    }
+}
+
+/// Unit direction that the equirectangular mapping sends to (u, v); the
+/// inverse of `sphere::get_sphere_uv`.
+pub fn equirect_direction(u: f32, v: f32) -> Vec3 {
+    let (theta, phi) = (v * PI, u * 2.0 * PI);
+    let sin_theta = theta.sin();
+    Vec3::new(-phi.cos() * sin_theta, -theta.cos(), phi.sin() * sin_theta)
+}
+
+/// Index of the bin of `cdf` that `xi` falls in, and how far into the bin
+/// it lies, in [0, 1).
+fn sample_cdf(cdf: &[f32], xi: f32) -> (usize, f32) {
+    let bins = cdf.len() - 1;
+    let index = cdf.partition_point(|&c| c <= xi).clamp(1, bins) - 1;
+    let width = cdf[index + 1] - cdf[index];
+    let offset = if width > 0.0 { ((xi - cdf[index]) / width).clamp(0.0, 0.999_999) } else { 0.5 };
+    (index, offset)
+}
+
+impl SampleLight for EnvironmentMap {
+    /// The map is infinitely far away, so the density doesn't depend on `origin`.
+    fn pdf_value(&self, _origin: Point3, direction: Vec3) -> f32 {
+        self.pdf(direction)
+    }
+
+    fn random(&self, _origin: Point3) -> Vec3 {
+        self.sample()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec3::random_unit_vector;
+
+    /// A dim 16x8 map with one bright pixel above the horizon.
+    fn map_with_sun() -> EnvironmentMap {
+        let (width, height) = (16, 8);
+        let pixels = (0..width * height)
+            .map(|i| if i == 2 * width + 5 { Color::new(500.0, 400.0, 300.0) } else { Color::new(0.2, 0.3, 0.5) })
+            .collect();
+        EnvironmentMap::new(Image::new(width, height, pixels), 30.0, 1.0)
+    }
+
+    #[test]
+    fn sample_cdf_finds_the_bin_and_offset() {
+        let cdf = [0.0, 0.25, 0.25, 1.0];
+        let (index, offset) = sample_cdf(&cdf, 0.1);
+        assert_eq!(index, 0);
+        assert!((offset - 0.4).abs() < 1e-6);
+        let (index, offset) = sample_cdf(&cdf, 0.625);
+        assert_eq!(index, 2);
+        assert!((offset - 0.5).abs() < 1e-6);
+        // The empty bin in the middle is never picked.
+        assert_eq!(sample_cdf(&cdf, 0.25).0, 2);
+        assert_eq!(sample_cdf(&cdf, 0.999_999).0, 2);
+    }
+
+    #[test]
+    fn cdfs_run_from_zero_to_one() {
+        let map = map_with_sun();
+        let width = map.image.width;
+        assert_eq!(map.marginal[0], 0.0);
+        assert!((map.marginal[map.image.height] - 1.0).abs() < 1e-5);
+        assert!(map.marginal.windows(2).all(|w| w[0] <= w[1]));
+        for row in map.conditional.chunks_exact(width + 1) {
+            assert_eq!(row[0], 0.0);
+            assert!((row[width] - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn pdf_integrates_to_one_over_the_sphere() {
+        let map = map_with_sun();
+        let n = 200_000;
+        let mean: f32 = (0..n).map(|_| map.pdf(random_unit_vector())).sum::<f32>() / n as f32;
+        assert!((mean * 4.0 * PI - 1.0).abs() < 0.05, "{}", mean * 4.0 * PI);
+    }
+
+    #[test]
+    fn samples_follow_the_pdf() {
+        // With directions drawn from the map, 1 / pdf averages to the solid
+        // angle of the whole sphere.
+        let map = map_with_sun();
+        let n = 200_000;
+        let mean: f32 = (0..n).map(|_| 1.0 / map.pdf(map.sample())).sum::<f32>() / n as f32;
+        assert!((mean / (4.0 * PI) - 1.0).abs() < 0.05, "{}", mean / (4.0 * PI));
+
+        // And the bright pixel gets about its share of them.
+        let sun = map.pixel_probability[2 * map.image.width + 5];
+        let hits = (0..n)
+            .filter(|_| {
+                let (x, y, _) = map.lookup(map.to_local.vector(map.sample()).unit());
+                (x, y) == (5, 2)
+            })
+            .count();
+        assert!((hits as f32 / n as f32 - sun).abs() < 0.01, "{} vs {}", hits as f32 / n as f32, sun);
+    }
+}
@@ -0,0 +1,98 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::color::{self, Color};
+
+pub enum ImageFormat {
+    Png,
+    Ppm,
+    Pfm,
+}
+
+impl ImageFormat {
+    /// Picks the format from the file extension: `.png` (8-bit sRGB), `.ppm`
+    /// (binary P6, 8-bit sRGB) or `.pfm` (linear float).
+    pub fn from_path(path: &Path) -> io::Result<Self> {
+        let extension = path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("png") => Ok(ImageFormat::Png),
+            Some("ppm") => Ok(ImageFormat::Ppm),
+            Some("pfm") => Ok(ImageFormat::Pfm),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported image format '{}' (expected .png, .ppm or .pfm)", path.display()),
+            )),
+        }
+    }
+}
+
+/// Image buffer holding linear radiance per pixel, stored row by row from the
+/// top-left corner. Conversion to a display encoding only happens on write.
+pub struct Film {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<Color>,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self { width, height, pixels: vec![Color::new(0.0, 0.0, 0.0); width * height] }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Color) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    /// Writes the image in the format implied by the file extension.
+    pub fn write(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        match ImageFormat::from_path(path)? {
+            ImageFormat::Png => self.write_png(path),
+            ImageFormat::Ppm => self.write_ppm(path),
+            ImageFormat::Pfm => self.write_pfm(path),
+        }
+    }
+
+    fn srgb_bytes(&self) -> Vec<u8> {
+        self.pixels.iter().flat_map(|&c| color::to_rgb8(c)).collect()
+    }
+
+    pub fn write_png(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        writer.write_image_data(&self.srgb_bytes()).map_err(io::Error::other)?;
+        writer.finish().map_err(io::Error::other)
+    }
+
+    pub fn write_ppm(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        write!(out, "P6\n{} {}\n255\n", self.width, self.height)?;
+        out.write_all(&self.srgb_bytes())?;
+        out.flush()
+    }
+
+    /// Portable float map: three little-endian floats per pixel, with rows
+    /// stored bottom to top as the format requires.
+    pub fn write_pfm(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        write!(out, "PF\n{} {}\n-1.0\n", self.width, self.height)?;
+        for y in (0..self.height).rev() {
+            for x in 0..self.width {
+                let c = self.get(x, y);
+                for component in [c.x, c.y, c.z] {
+                    out.write_all(&component.to_le_bytes())?;
+                }
+            }
+        }
+        out.flush()
+    }
+}
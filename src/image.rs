@@ -0,0 +1,360 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::Path;
+
+use crate::color::Color;
+
+/// Image loaded from disk, stored as linear RGB row by row from the top-left
+/// corner. 8-bit formats are assumed to be sRGB encoded and are linearized on
+/// load; the HDR formats are already linear.
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<Color>,
+}
+
+fn invalid(path: &Path, message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), message.into()))
+}
+
+/// Inverse of `color::linear_to_srgb`.
+pub fn srgb_to_linear(srgb_component: f32) -> f32 {
+    if srgb_component <= 0.04045 {
+        srgb_component / 12.92
+    } else {
+        ((srgb_component + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+impl Image {
+    /// Loads a `.png`, `.ppm` (P3 or P6), `.hdr` (Radiance RGBE) or `.pfm` file.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("png") => Self::load_png(path),
+            Some("ppm") => Self::load_ppm(path),
+            Some("hdr") => Self::load_hdr(path),
+            Some("pfm") => Self::load_pfm(path),
+            _ => Err(invalid(path, "unsupported image format (expected .png, .ppm, .hdr or .pfm)")),
+        }
+    }
+
+    /// Image from linear `pixels`, given row by row from the top-left corner.
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> Self {
+        assert_eq!(pixels.len(), width * height);
+        Self { width, height, pixels }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    fn from_srgb8(width: usize, height: usize, bytes: &[u8]) -> Self {
+        let pixels = bytes
+            .chunks_exact(3)
+            .map(|rgb| {
+                Color::new(
+                    srgb_to_linear(rgb[0] as f32 / 255.0),
+                    srgb_to_linear(rgb[1] as f32 / 255.0),
+                    srgb_to_linear(rgb[2] as f32 / 255.0),
+                )
+            })
+            .collect();
+        Self { width, height, pixels }
+    }
+
+    fn load_png(path: &Path) -> io::Result<Self> {
+        let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+        // Expand palettes and low bit depths, and reduce 16-bit to 8-bit, so
+        // every image arrives as 8-bit gray, gray+alpha, RGB or RGBA.
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(|e| invalid(path, e.to_string()))?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).map_err(|e| invalid(path, e.to_string()))?;
+        let bytes = &buf[..info.buffer_size()];
+
+        let rgb: Vec<u8> = match info.color_type {
+            png::ColorType::Rgb => bytes.to_vec(),
+            png::ColorType::Rgba => bytes.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect(),
+            png::ColorType::Grayscale => bytes.iter().flat_map(|&g| [g, g, g]).collect(),
+            png::ColorType::GrayscaleAlpha => bytes.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0]]).collect(),
+            png::ColorType::Indexed => return Err(invalid(path, "unexpanded palette image")),
+        };
+        Ok(Self::from_srgb8(info.width as usize, info.height as usize, &rgb))
+    }
+
+    fn load_ppm(path: &Path) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        let (fields, pos) = header_fields(path, &data)?;
+
+        let number = |s: &str| s.parse::<usize>().map_err(|_| invalid(path, format!("bad header field '{}'", s)));
+        let width = number(&fields[1])?;
+        let height = number(&fields[2])?;
+        let max_value = number(&fields[3])?;
+        if max_value == 0 || max_value > 255 {
+            return Err(invalid(path, format!("unsupported maximum value {}", max_value)));
+        }
+        let count = pixel_data_size(path, width, height, 3)?;
+
+        let samples: Vec<u8> = match fields[0].as_str() {
+            "P6" => data.get(pos..).and_then(|rest| rest.get(..count)).ok_or_else(|| invalid(path, "truncated pixel data"))?.to_vec(),
+            "P3" => {
+                let text = String::from_utf8_lossy(data.get(pos..).unwrap_or(&[]));
+                let samples = text
+                    .split_whitespace()
+                    .take(count)
+                    .map(|s| s.parse::<u8>().map_err(|_| invalid(path, format!("bad sample '{}'", s))))
+                    .collect::<io::Result<Vec<u8>>>()?;
+                if samples.len() < count {
+                    return Err(invalid(path, "truncated pixel data"));
+                }
+                samples
+            }
+            magic => return Err(invalid(path, format!("unsupported PPM type '{}'", magic))),
+        };
+
+        let scaled: Vec<u8> = samples.iter().map(|&s| (s as u32 * 255 / max_value as u32) as u8).collect();
+        Ok(Self::from_srgb8(width, height, &scaled))
+    }
+
+    /// Loads a Radiance RGBE file, flat or with run-length encoded scanlines.
+    /// Only the standard `-Y height +X width` orientation is supported.
+    fn load_hdr(path: &Path) -> io::Result<Self> {
+        let data = fs::read(path)?;
+
+        // Header lines run up to an empty line, and the resolution line follows.
+        let mut pos = 0;
+        let mut next_line = || {
+            if pos >= data.len() {
+                return Err(invalid(path, "truncated header"));
+            }
+            let start = pos;
+            while pos < data.len() && data[pos] != b'\n' {
+                pos += 1;
+            }
+            let line = String::from_utf8_lossy(&data[start..pos]).into_owned();
+            pos += 1;
+            Ok(line)
+        };
+        let magic = next_line()?;
+        if !magic.starts_with("#?") {
+            return Err(invalid(path, "not a Radiance HDR file"));
+        }
+        loop {
+            let line = next_line()?;
+            if line.is_empty() {
+                break;
+            }
+            if line.starts_with("FORMAT=") && line != "FORMAT=32-bit_rle_rgbe" {
+                return Err(invalid(path, format!("unsupported {}", line)));
+            }
+        }
+        let resolution = next_line()?;
+        let (height, width) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+            ["-Y", h, "+X", w] => (h.parse::<usize>(), w.parse::<usize>()),
+            _ => return Err(invalid(path, format!("unsupported resolution line '{}'", resolution))),
+        };
+        let (height, width) = match (height, width) {
+            (Ok(h), Ok(w)) => (h, w),
+            _ => return Err(invalid(path, format!("bad resolution line '{}'", resolution))),
+        };
+        let size = pixel_data_size(path, width, height, 4)?;
+
+        let truncated = || invalid(path, "truncated pixel data");
+        let mut rgbe = vec![[0u8; 4]; size / 4];
+        for row in rgbe.chunks_exact_mut(width) {
+            let header = data.get(pos..pos + 4).ok_or_else(truncated)?;
+            let rle = (8..0x8000).contains(&width) && header[0] == 2 && header[1] == 2 && header[2] & 0x80 == 0;
+            if !rle {
+                for pixel in row.iter_mut() {
+                    pixel.copy_from_slice(data.get(pos..pos + 4).ok_or_else(truncated)?);
+                    pos += 4;
+                }
+                continue;
+            }
+            if ((header[2] as usize) << 8 | header[3] as usize) != width {
+                return Err(invalid(path, "scanline width mismatch"));
+            }
+            pos += 4;
+
+            // Each of the four channels is stored separately as a mix of runs
+            // (count > 128) and literal spans.
+            for channel in 0..4 {
+                let mut x = 0;
+                while x < width {
+                    let count = *data.get(pos).ok_or_else(truncated)? as usize;
+                    pos += 1;
+                    if count > 128 {
+                        let count = count - 128;
+                        let value = *data.get(pos).ok_or_else(truncated)?;
+                        pos += 1;
+                        if x + count > width {
+                            return Err(invalid(path, "run past end of scanline"));
+                        }
+                        for pixel in &mut row[x..x + count] {
+                            pixel[channel] = value;
+                        }
+                        x += count;
+                    } else {
+                        if count == 0 || x + count > width {
+                            return Err(invalid(path, "bad scanline span"));
+                        }
+                        let values = data.get(pos..pos + count).ok_or_else(truncated)?;
+                        for (pixel, &value) in row[x..x + count].iter_mut().zip(values) {
+                            pixel[channel] = value;
+                        }
+                        pos += count;
+                        x += count;
+                    }
+                }
+            }
+        }
+
+        let pixels = rgbe
+            .iter()
+            .map(|&[r, g, b, e]| {
+                if e == 0 {
+                    return Color::new(0.0, 0.0, 0.0);
+                }
+                let scale = 2f32.powi(e as i32 - 136);
+                Color::new(r as f32 * scale, g as f32 * scale, b as f32 * scale)
+            })
+            .collect();
+        Ok(Self { width, height, pixels })
+    }
+
+    /// Loads a color (`PF`) or grayscale (`Pf`) portable float map.
+    fn load_pfm(path: &Path) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        let (fields, pos) = header_fields(path, &data)?;
+
+        let channels = match fields[0].as_str() {
+            "PF" => 3,
+            "Pf" => 1,
+            magic => return Err(invalid(path, format!("unsupported PFM type '{}'", magic))),
+        };
+        let number = |s: &str| s.parse::<usize>().map_err(|_| invalid(path, format!("bad header field '{}'", s)));
+        let width = number(&fields[1])?;
+        let height = number(&fields[2])?;
+        // The sign of the scale gives the byte order, its magnitude a factor
+        // for every sample.
+        let scale: f32 = fields[3].parse().map_err(|_| invalid(path, format!("bad scale '{}'", fields[3])))?;
+        let little_endian = scale < 0.0;
+
+        let size = pixel_data_size(path, width, height, channels * 4)?;
+        let bytes = data.get(pos..).and_then(|rest| rest.get(..size)).ok_or_else(|| invalid(path, "truncated pixel data"))?;
+        let samples: Vec<f32> = bytes
+            .chunks_exact(4)
+            .map(|b| {
+                let b = [b[0], b[1], b[2], b[3]];
+                scale.abs() * if little_endian { f32::from_le_bytes(b) } else { f32::from_be_bytes(b) }
+            })
+            .collect();
+
+        // Rows are stored bottom to top.
+        let mut pixels = Vec::with_capacity(width * height);
+        for row in samples.chunks_exact(width * channels).rev() {
+            pixels.extend(row.chunks_exact(channels).map(|s| match s {
+                [r, g, b] => Color::new(*r, *g, *b),
+                _ => Color::new(s[0], s[0], s[0]),
+            }));
+        }
+        Ok(Self { width, height, pixels })
+    }
+}
+
+/// Size of the pixel data of a `width` by `height` image with
+/// `bytes_per_pixel`, refusing empty images and sizes that don't fit in memory.
+fn pixel_data_size(path: &Path, width: usize, height: usize, bytes_per_pixel: usize) -> io::Result<usize> {
+    if width == 0 || height == 0 {
+        return Err(invalid(path, format!("empty {}x{} image", width, height)));
+    }
+    width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(bytes_per_pixel))
+        .ok_or_else(|| invalid(path, format!("{}x{} image is too large", width, height)))
+}
+
+/// Reads the four whitespace-separated header fields shared by PPM and PFM,
+/// skipping comments between them. Returns the fields and the offset of the
+/// pixel data, which starts after a single whitespace byte.
+fn header_fields(path: &Path, data: &[u8]) -> io::Result<(Vec<String>, usize)> {
+    let mut fields = Vec::new();
+    let mut pos = 0;
+    while fields.len() < 4 {
+        while pos < data.len() && data[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if pos < data.len() && data[pos] == b'#' {
+            while pos < data.len() && data[pos] != b'\n' {
+                pos += 1;
+            }
+            continue;
+        }
+        let start = pos;
+        while pos < data.len() && !data[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if start == pos {
+            return Err(invalid(path, "truncated header"));
+        }
+        fields.push(String::from_utf8_lossy(&data[start..pos]).into_owned());
+    }
+    Ok((fields, pos + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `contents` to a file called `name` and loads it as an image.
+    fn load(name: &str, contents: &[u8]) -> io::Result<Image> {
+        let dir = std::env::temp_dir().join(format!("pobert-image-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, contents).unwrap();
+        Image::load(path)
+    }
+
+    fn rgb(c: Color) -> [f32; 3] {
+        [c.x, c.y, c.z]
+    }
+
+    fn load_error(name: &str, contents: &[u8]) -> String {
+        match load(name, contents) {
+            Ok(_) => panic!("{} loaded without error", name),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn reads_flat_hdr_and_pfm() {
+        let hdr = load("flat.hdr", b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 2\n\x80\x40\x00\x81\x00\x00\x00\x00").unwrap();
+        assert_eq!((hdr.width, hdr.height), (2, 1));
+        assert_eq!(rgb(hdr.pixel(0, 0)), [1.0, 0.5, 0.0]);
+        assert_eq!(rgb(hdr.pixel(1, 0)), [0.0, 0.0, 0.0]);
+
+        // Rows are stored bottom to top.
+        let mut pfm = b"Pf\n1 2\n-1.0\n".to_vec();
+        pfm.extend([0.25f32, 0.75].iter().flat_map(|s| s.to_le_bytes()));
+        let pfm = load("gray.pfm", &pfm).unwrap();
+        assert_eq!(rgb(pfm.pixel(0, 0)), [0.75, 0.75, 0.75]);
+        assert_eq!(rgb(pfm.pixel(0, 1)), [0.25, 0.25, 0.25]);
+    }
+
+    #[test]
+    fn rejects_truncated_headers() {
+        assert!(load_error("magic.hdr", b"#?RADIANCE").contains("truncated header"));
+        assert!(load_error("header.hdr", b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n").contains("truncated header"));
+        assert!(load_error("resolution.hdr", b"#?RADIANCE\n\n").contains("truncated header"));
+    }
+
+    #[test]
+    fn rejects_empty_and_oversized_images() {
+        assert!(load_error("empty.hdr", b"#?RADIANCE\n\n-Y 0 +X 0\n").contains("empty 0x0 image"));
+        assert!(load_error("empty.pfm", b"PF\n0 4\n-1.0\n").contains("empty 0x4 image"));
+        let huge = format!("PF\n{} {}\n-1.0\n", usize::MAX / 2, 3);
+        assert!(load_error("huge.pfm", huge.as_bytes()).contains("too large"));
+    }
+}
@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use crate::aabb::{Aabb, BoundingBox};
+use crate::interval::Interval;
+use crate::ray::{Direction, Origin, Ray, Time};
+use crate::sphere::{Hit, HitRecord, HittableObject};
+use crate::transform::Transform;
+use crate::vec3::Unit;
+
+/// A shared object placed in the world by an affine transform. Any number of
+/// instances can point at the same object, so a mesh or a BVH of spheres is
+/// built once and drawn many times.
+#[derive(Clone)]
+pub struct Instance {
+    object: Arc<HittableObject>,
+    transform: Transform,
+    bbox: Aabb,
+}
+
+impl Instance {
+    pub fn new(object: Arc<HittableObject>, transform: Transform) -> Self {
+        let bbox = transform.bounding_box(object.bounding_box());
+        Self { object, transform, bbox }
+    }
+}
+
+impl Hit for Instance {
+    fn hit<'a>(&'a self, r: Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        // The direction is transformed without normalizing, so `t` means the
+        // same in both spaces and `ray_t` carries over unchanged.
+        let to_object = self.transform.inverse();
+        let object_ray = Ray::with_time(to_object.point(r.origin()), to_object.vector(r.direction()), r.time());
+        if !self.object.hit(object_ray, ray_t, rec) {
+            return false;
+        }
+
+        // The object already flipped the normal against the ray; the inverse
+        // transpose keeps that orientation, so `front_face` stays valid.
+        rec.p = self.transform.point(rec.p);
+        rec.normal = self.transform.normal(rec.normal).unit();
+        true
+    }
+}
+
+impl BoundingBox for Instance {
+    fn bounding_box(&self) -> Aabb {
+        self.bbox
+    }
+}
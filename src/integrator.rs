@@ -0,0 +1,667 @@
+use crate::camera::{count_ray, Background};
+use crate::color::Color;
+use crate::interval::Interval;
+use crate::light::{Light, SampleLight};
+use crate::punctual::Illuminate;
+use crate::material::{Emitted, Interior, Material, Scatter, ScatterRecord, ScatteringValue};
+use crate::medium::MediumStack;
+use crate::pdf::{CosinePdf, LightPdf, MixturePdf, Pdf, SamplePdf};
+use crate::ray::{Direction, Origin, Ray, Time};
+use crate::rtweekend::{random_double, INF};
+use crate::spectrum::{wavelength_rgb, Spectral, Wavelengths, LAMBDA_MAX, LAMBDA_MIN};
+use crate::sphere::{Hit, HitRecord, HittableList, New};
+use crate::vec3::{element_wise_mul, Dot, Length, NearZero, Vec3};
+
+/// Bounces a path gets before Russian roulette can end it, unless configured.
+pub const DEFAULT_RR_DEPTH: i32 = 5;
+
+/// What an integrator sees of the scene being rendered.
+#[derive(Clone, Copy)]
+pub struct RenderContext<'a> {
+    pub world: &'a HittableList,
+    /// Lights that can be sampled by direction.
+    pub lights: &'a [Light],
+    /// Point, spot and directional lights, reached only by shadow rays.
+    pub delta_lights: &'a [Light],
+    pub background: &'a Background,
+    /// In spectral mode, the conversion from scene colors and the
+    /// wavelengths the current path carries. Colors along the path then
+    /// hold the values at those wavelengths rather than RGB.
+    pub spectral: Option<(&'a Spectral, Wavelengths)>,
+    /// Wavelength in nm the current path has been narrowed down to by a
+    /// dispersive material, if any.
+    pub dispersed_wavelength: Option<f32>,
+    /// Clear media the current path is inside, which absorb light along
+    /// every stretch it travels.
+    pub media: MediumStack,
+}
+
+impl RenderContext<'_> {
+    /// A reflectance or other factor on the light from the scene, in the
+    /// form the path carries colors in.
+    fn factor(&self, rgb: Color) -> Color {
+        match &self.spectral {
+            Some((spectral, wavelengths)) => spectral.reflectance(rgb, wavelengths),
+            None => rgb,
+        }
+    }
+
+    /// Radiance or irradiance from the scene, in the form the path carries
+    /// colors in.
+    fn radiance(&self, rgb: Color) -> Color {
+        match &self.spectral {
+            Some((spectral, wavelengths)) => spectral.illuminant(rgb, wavelengths),
+            None => rgb,
+        }
+    }
+
+    /// Radiance arriving along `r` from outside the scene.
+    fn background(&self, r: &Ray) -> Color {
+        self.radiance(self.background.value(r))
+    }
+
+    /// Radiance emitted by the surface at `rec`.
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        self.radiance(rec.mat.emitted(rec.u, rec.v, rec.p))
+    }
+}
+
+/// Estimates the light arriving at the camera along a ray. `depth` is the
+/// number of bounces the integrator may still follow.
+pub trait Integrator {
+    fn ray_color(&self, r: Ray, depth: i32, ctx: &RenderContext) -> Color;
+}
+
+/// The integrators a scene can be rendered with, from the fast previews to
+/// the full path tracers.
+#[derive(Clone, Copy)]
+pub enum IntegratorKind {
+    Path(PathIntegrator),
+    Mis(MisIntegrator),
+    Whitted(WhittedIntegrator),
+    AmbientOcclusion(AmbientOcclusionIntegrator),
+    DirectLighting(DirectLightingIntegrator),
+}
+
+/// Path tracing that draws each bounce from an even mixture of the
+/// material's density and the lights.
+#[derive(Clone, Copy)]
+pub struct PathIntegrator {
+    /// Bounces after which Russian roulette may end the path.
+    pub rr_depth: i32,
+}
+
+/// Path tracing that takes one light sample and one material sample at each
+/// bounce and combines them with multiple importance sampling.
+#[derive(Clone, Copy)]
+pub struct MisIntegrator {
+    pub heuristic: Heuristic,
+    /// Bounces after which Russian roulette may end the path.
+    pub rr_depth: i32,
+}
+
+/// Classic recursive ray tracing: every light is sampled once at diffuse and
+/// glossy hits, mirrors and glass are followed, and there is no indirect
+/// diffuse light.
+#[derive(Clone, Copy)]
+pub struct WhittedIntegrator;
+
+/// Fraction of the hemisphere above the first hit that is open within
+/// `distance`, shown as a gray level. Misses count as fully open.
+#[derive(Clone, Copy)]
+pub struct AmbientOcclusionIntegrator {
+    pub distance: f32,
+}
+
+/// Light reaching the first diffuse or glossy hit straight from emitters,
+/// found by MIS over light and material samples. Mirrors and glass on the
+/// way there are followed; light bouncing off other surfaces is ignored.
+#[derive(Clone, Copy)]
+pub struct DirectLightingIntegrator;
+
+/// Weighting used to combine the two strategies of multiple importance sampling.
+#[derive(Clone, Copy)]
+pub enum Heuristic {
+    Balance,
+    /// Balance heuristic with squared pdfs, which favours whichever strategy
+    /// is clearly better for a given direction.
+    Power,
+}
+
+impl Heuristic {
+    /// MIS weight for a sample taken with density `pdf` when the other
+    /// strategy would have produced it with density `other`.
+    pub fn weight(&self, pdf: f32, other: f32) -> f32 {
+        let (a, b) = match self {
+            Heuristic::Balance => (pdf, other),
+            Heuristic::Power => (pdf * pdf, other * other),
+        };
+        if a + b > 0.0 { a / (a + b) } else { 0.0 }
+    }
+}
+
+impl Integrator for IntegratorKind {
+    fn ray_color(&self, r: Ray, depth: i32, ctx: &RenderContext) -> Color {
+        match self {
+            IntegratorKind::Path(integrator) => integrator.ray_color(r, depth, ctx),
+            IntegratorKind::Mis(integrator) => integrator.ray_color(r, depth, ctx),
+            IntegratorKind::Whitted(integrator) => integrator.ray_color(r, depth, ctx),
+            IntegratorKind::AmbientOcclusion(integrator) => integrator.ray_color(r, depth, ctx),
+            IntegratorKind::DirectLighting(integrator) => integrator.ray_color(r, depth, ctx),
+        }
+    }
+}
+
+fn black() -> Color {
+    Color::new(0.0, 0.0, 0.0)
+}
+
+fn is_black(color: Color) -> bool {
+    color.x <= 0.0 && color.y <= 0.0 && color.z <= 0.0
+}
+
+/// Scatters `r` off the material at `rec` at the wavelength the path stands
+/// for, and returns the weight to put on the path, or `None` when the
+/// material absorbs the ray.
+///
+/// A dispersive material sends every wavelength its own way, so the path
+/// goes on with just one. In spectral mode that is the hero wavelength,
+/// which counts three times over as one of the three the path carried. In
+/// RGB it is drawn uniformly over the traced range, and the path takes on
+/// the color of light of that wavelength, which averages to white. `ctx`
+/// keeps the wavelength for any dispersion further along.
+fn scatter_at_wavelength(r: &Ray, rec: &mut HitRecord, srec: &mut ScatterRecord, ctx: &mut RenderContext) -> Option<Color> {
+    srec.wavelength = match (ctx.dispersed_wavelength, &ctx.spectral) {
+        (Some(lambda), _) => lambda,
+        (None, Some((_, wavelengths))) => wavelengths.hero(),
+        (None, None) => LAMBDA_MIN + random_double() * (LAMBDA_MAX - LAMBDA_MIN),
+    };
+    let mat = rec.mat;
+    if !mat.scatter(r, rec, srec) {
+        return None;
+    }
+    if !srec.dispersed || ctx.dispersed_wavelength.is_some() {
+        return Some(Color::new(1.0, 1.0, 1.0));
+    }
+    ctx.dispersed_wavelength = Some(srec.wavelength);
+    // In spectral mode the hero wavelength is the first.
+    match ctx.spectral {
+        Some(_) => Some(Color::new(3.0, 0.0, 0.0)),
+        None => Some(wavelength_rgb(srec.wavelength)),
+    }
+}
+
+/// Traces `r` into the world, counting it for the rays/s statistic.
+fn trace<'a>(r: Ray, ctx: &RenderContext<'a>, rec: &mut HitRecord<'a>) -> bool {
+    count_ray();
+    ctx.world.hit(r, Interval::new(0.001, INF), rec)
+}
+
+/// What `r` hits first in the world, or `None` when it escapes.
+fn first_hit<'a>(r: Ray, ctx: &RenderContext<'a>) -> Option<HitRecord<'a>> {
+    let mut rec = HitRecord::new();
+    trace(r, ctx, &mut rec).then_some(rec)
+}
+
+/// Radiance emitted towards `r` by `hit`, its first hit, without any
+/// further bouncing. That is the background when the ray escaped the scene.
+fn emitted_by(r: Ray, hit: Option<&HitRecord>, ctx: &RenderContext) -> Color {
+    match hit {
+        Some(rec) => ctx.emitted(rec),
+        None => ctx.background(&r),
+    }
+}
+
+/// The media the path is in once it leaves `rec` along `direction`. Going
+/// through the surface of a clear material enters the medium inside when
+/// hit from the front, and leaves it when hit from behind.
+fn media_after(rec: &HitRecord, direction: Vec3, ctx: &RenderContext) -> MediumStack {
+    match rec.mat.interior() {
+        Some(absorption) if direction.dot(rec.normal) < 0.0 => ctx.media.crossed(absorption, rec.front_face),
+        _ => ctx.media,
+    }
+}
+
+/// Share of light that survives `r` through `media`, from its origin up to
+/// `hit`, its first hit, or out of the scene when it escaped.
+fn transmittance(r: Ray, hit: Option<&HitRecord>, media: &MediumStack, ctx: &RenderContext) -> Color {
+    let t = hit.map_or(INF, |rec| rec.t);
+    ctx.factor(media.transmittance(t * r.direction().length()))
+}
+
+/// Density with which light sampling finds the emission `r` runs into at
+/// `hit`: only a light that is the first hit along `r` gets credited, so
+/// only those lights count. Emitters that aren't among the lights give 0.
+fn light_pdf(r: Ray, hit: Option<&HitRecord>, ctx: &RenderContext) -> f32 {
+    if ctx.lights.is_empty() {
+        return 0.0;
+    }
+    let sum: f32 = ctx
+        .lights
+        .iter()
+        .map(|light| match light.pdf_value(r.origin(), r.direction()) {
+            pdf if pdf > 0.0 && light.is_first_hit(r, hit) => pdf,
+            _ => 0.0,
+        })
+        .sum();
+    sum / ctx.lights.len() as f32
+}
+
+/// One-sample estimate of light arriving at `rec` straight from the lights,
+/// plus the density the material would have sampled the same direction
+/// with, for MIS. `None` when the sample can't contribute, which includes
+/// the sampled light being hidden behind something else.
+fn sample_lights(r_in: &Ray, rec: &HitRecord, mat: &Material, srec: &ScatterRecord, ctx: &RenderContext) -> Option<(Color, f32, f32)> {
+    if ctx.lights.is_empty() {
+        return None;
+    }
+    let light = LightPdf::new(ctx.lights, rec.p).choose();
+    let to_light = Ray::with_time(rec.p, light.random(rec.p), r_in.time());
+    let value = ctx.factor(mat.scattering_value(r_in, rec, srec, &to_light));
+    if is_black(value) {
+        return None;
+    }
+    let hit = first_hit(to_light, ctx);
+    if !light.is_first_hit(to_light, hit.as_ref()) {
+        return None;
+    }
+    let pdf_light = light_pdf(to_light, hit.as_ref(), ctx);
+    if pdf_light <= 0.0 {
+        return None;
+    }
+    let radiance = element_wise_mul(
+        emitted_by(to_light, hit.as_ref(), ctx),
+        transmittance(to_light, hit.as_ref(), &media_after(rec, to_light.direction(), ctx), ctx),
+    );
+    let contribution = element_wise_mul(value, radiance) / pdf_light;
+    Some((contribution, pdf_light, srec.pdf.value(to_light.direction())))
+}
+
+/// Light arriving at `rec` from the punctual lights, with one shadow ray
+/// each. No other strategy can find these lights, so nothing needs weighting.
+fn illuminate_delta(r_in: &Ray, rec: &HitRecord, mat: &Material, srec: &ScatterRecord, ctx: &RenderContext) -> Color {
+    let mut total = black();
+    for light in ctx.delta_lights {
+        let Some((direction, distance, irradiance)) = light.illuminate(rec.p) else {
+            continue;
+        };
+        let to_light = Ray::with_time(rec.p, direction, r_in.time());
+        let value = ctx.factor(mat.scattering_value(r_in, rec, srec, &to_light));
+        if is_black(value) {
+            continue;
+        }
+        count_ray();
+        let mut occluder = HitRecord::new();
+        if ctx.world.hit(to_light, Interval::new(0.001, distance - 0.001), &mut occluder) {
+            continue;
+        }
+        let along = media_after(rec, direction, ctx).transmittance(distance * direction.length());
+        total = total + element_wise_mul(value, element_wise_mul(ctx.factor(along), ctx.radiance(irradiance)));
+    }
+    total
+}
+
+/// Russian roulette: past `rr_depth` bounces a path survives with a
+/// probability that follows its throughput's largest channel, and survivors
+/// are reweighted so the estimate stays unbiased. Dim paths end early while
+/// bright ones, such as those through clear glass, keep going. The largest
+/// channel, rather than luminance, keeps a path that only carries blue from
+/// being cut short and reweighted into fireflies, and it stays positive
+/// when a spectral throughput has negative channels. Returns false when the
+/// path should end.
+fn russian_roulette(throughput: &mut Color, bounce: i32, rr_depth: i32) -> bool {
+    if bounce < rr_depth {
+        return true;
+    }
+    let survival = throughput.x.abs().max(throughput.y.abs()).max(throughput.z.abs()).min(0.95);
+    if survival <= 0.0 || random_double() >= survival {
+        return false;
+    }
+    *throughput = *throughput / survival;
+    true
+}
+
+impl Integrator for PathIntegrator {
+    fn ray_color(&self, r: Ray, depth: i32, ctx: &RenderContext) -> Color {
+        let mut ctx = *ctx;
+        let ctx = &mut ctx;
+        let mut color = black();
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut r = r;
+
+        for bounce in 0..depth {
+            let hit = first_hit(r, ctx);
+            throughput = element_wise_mul(throughput, transmittance(r, hit.as_ref(), &ctx.media, ctx));
+            let Some(mut rec) = hit else {
+                color = color + element_wise_mul(throughput, ctx.background(&r));
+                break;
+            };
+
+            color = color + element_wise_mul(throughput, ctx.emitted(&rec));
+
+            let mut srec = ScatterRecord::new();
+            let mat = rec.mat;
+            let Some(weight) = scatter_at_wavelength(&r, &mut rec, &mut srec, ctx) else {
+                break;
+            };
+            throughput = element_wise_mul(throughput, weight);
+
+            if srec.skip_pdf {
+                throughput = element_wise_mul(throughput, ctx.factor(srec.attenuation));
+                ctx.media = media_after(&rec, srec.skip_pdf_ray.direction(), ctx);
+                r = srec.skip_pdf_ray;
+            } else {
+                color = color + element_wise_mul(throughput, illuminate_delta(&r, &rec, mat, &srec, ctx));
+
+                let light_pdf = if ctx.lights.is_empty() { None } else { Some(Pdf::Light(LightPdf::new(ctx.lights, rec.p))) };
+                let mixture;
+                let pdf = match &light_pdf {
+                    Some(light_pdf) => {
+                        mixture = Pdf::Mixture(MixturePdf::new(light_pdf, &srec.pdf));
+                        &mixture
+                    }
+                    None => &srec.pdf,
+                };
+
+                let scattered = Ray::with_time(rec.p, pdf.generate(), r.time());
+                if scattered.direction().near_zero() {
+                    break;
+                }
+                let pdf_value = pdf.value(scattered.direction());
+                if pdf_value <= 0.0 {
+                    break;
+                }
+
+                let value = ctx.factor(mat.scattering_value(&r, &rec, &srec, &scattered));
+                throughput = element_wise_mul(throughput, value) / pdf_value;
+                ctx.media = media_after(&rec, scattered.direction(), ctx);
+                r = scattered;
+            }
+
+            if !russian_roulette(&mut throughput, bounce + 1, self.rr_depth) {
+                break;
+            }
+        }
+
+        color
+    }
+}
+
+impl Integrator for MisIntegrator {
+    /// Emission reached by a material sample is weighted against light
+    /// sampling using `bsdf_pdf`, the density that sample was drawn with.
+    /// It is `None` for the camera ray and after specular bounces, where
+    /// light sampling wasn't possible.
+    fn ray_color(&self, r: Ray, depth: i32, ctx: &RenderContext) -> Color {
+        let mut ctx = *ctx;
+        let ctx = &mut ctx;
+        let mut color = black();
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut r = r;
+        let mut bsdf_pdf: Option<f32> = None;
+
+        for bounce in 0..depth {
+            // The background counts as emission too, since an environment
+            // map among the lights gets sampled like any other light.
+            let hit = first_hit(r, ctx);
+            throughput = element_wise_mul(throughput, transmittance(r, hit.as_ref(), &ctx.media, ctx));
+            let mut emitted = emitted_by(r, hit.as_ref(), ctx);
+            if let Some(bsdf_pdf) = bsdf_pdf {
+                emitted = emitted * self.heuristic.weight(bsdf_pdf, light_pdf(r, hit.as_ref(), ctx));
+            }
+            color = color + element_wise_mul(throughput, emitted);
+            let Some(mut rec) = hit else {
+                break;
+            };
+
+            let mut srec = ScatterRecord::new();
+            let mat = rec.mat;
+            let Some(weight) = scatter_at_wavelength(&r, &mut rec, &mut srec, ctx) else {
+                break;
+            };
+            throughput = element_wise_mul(throughput, weight);
+
+            if srec.skip_pdf {
+                throughput = element_wise_mul(throughput, ctx.factor(srec.attenuation));
+                ctx.media = media_after(&rec, srec.skip_pdf_ray.direction(), ctx);
+                r = srec.skip_pdf_ray;
+                bsdf_pdf = None;
+            } else {
+                // Light sampling, which counts only the light it aimed for.
+                if let Some((contribution, pdf_light, pdf_bsdf)) = sample_lights(&r, &rec, mat, &srec, ctx) {
+                    let weight = self.heuristic.weight(pdf_light, pdf_bsdf);
+                    color = color + element_wise_mul(throughput, contribution) * weight;
+                }
+                color = color + element_wise_mul(throughput, illuminate_delta(&r, &rec, mat, &srec, ctx));
+
+                // Material sampling, which continues the path.
+                let scattered = Ray::with_time(rec.p, srec.pdf.generate(), r.time());
+                if scattered.direction().near_zero() {
+                    break;
+                }
+                let pdf_bsdf = srec.pdf.value(scattered.direction());
+                if pdf_bsdf <= 0.0 {
+                    break;
+                }
+                let value = ctx.factor(mat.scattering_value(&r, &rec, &srec, &scattered));
+                throughput = element_wise_mul(throughput, value) / pdf_bsdf;
+                ctx.media = media_after(&rec, scattered.direction(), ctx);
+                r = scattered;
+                bsdf_pdf = Some(pdf_bsdf);
+            }
+
+            if !russian_roulette(&mut throughput, bounce + 1, self.rr_depth) {
+                break;
+            }
+        }
+
+        color
+    }
+}
+
+impl Integrator for WhittedIntegrator {
+    fn ray_color(&self, r: Ray, depth: i32, ctx: &RenderContext) -> Color {
+        if depth <= 0 {
+            return black();
+        }
+        let hit = first_hit(r, ctx);
+        let along = transmittance(r, hit.as_ref(), &ctx.media, ctx);
+        let Some(mut rec) = hit else {
+            return element_wise_mul(along, ctx.background(&r));
+        };
+
+        let emitted = ctx.emitted(&rec);
+
+        let mut ctx = *ctx;
+        let ctx = &mut ctx;
+        let mut srec = ScatterRecord::new();
+        let mat = rec.mat;
+        let Some(weight) = scatter_at_wavelength(&r, &mut rec, &mut srec, ctx) else {
+            return element_wise_mul(along, emitted);
+        };
+
+        if srec.skip_pdf {
+            ctx.media = media_after(&rec, srec.skip_pdf_ray.direction(), ctx);
+            let attenuation = element_wise_mul(weight, ctx.factor(srec.attenuation));
+            let reflected = element_wise_mul(attenuation, self.ray_color(srec.skip_pdf_ray, depth - 1, ctx));
+            return element_wise_mul(along, emitted + reflected);
+        }
+
+        // One shadow ray per light, each towards a random point on it.
+        let mut direct = black();
+        for light in ctx.lights {
+            let to_light = Ray::with_time(rec.p, light.random(rec.p), r.time());
+            let pdf_light = light.pdf_value(rec.p, to_light.direction());
+            if pdf_light <= 0.0 {
+                continue;
+            }
+            let value = ctx.factor(mat.scattering_value(&r, &rec, &srec, &to_light));
+            if is_black(value) {
+                continue;
+            }
+            let hit = first_hit(to_light, ctx);
+            if !light.is_first_hit(to_light, hit.as_ref()) {
+                continue;
+            }
+            let radiance = element_wise_mul(
+                emitted_by(to_light, hit.as_ref(), ctx),
+                transmittance(to_light, hit.as_ref(), &media_after(&rec, to_light.direction(), ctx), ctx),
+            );
+            direct = direct + element_wise_mul(value, radiance) / pdf_light;
+        }
+        element_wise_mul(along, emitted + element_wise_mul(weight, direct + illuminate_delta(&r, &rec, mat, &srec, ctx)))
+    }
+}
+
+impl Integrator for AmbientOcclusionIntegrator {
+    fn ray_color(&self, r: Ray, _depth: i32, ctx: &RenderContext) -> Color {
+        let white = ctx.radiance(Color::new(1.0, 1.0, 1.0));
+        let mut rec = HitRecord::new();
+        if !trace(r, ctx, &mut rec) {
+            return white;
+        }
+
+        // Cosine-weighted sampling makes the estimate a plain hit-or-miss.
+        let direction = CosinePdf::new(rec.normal).generate();
+        let probe = Ray::with_time(rec.p, direction, r.time());
+        let mut occluder = HitRecord::new();
+        count_ray();
+        if ctx.world.hit(probe, Interval::new(0.001, self.distance), &mut occluder) {
+            black()
+        } else {
+            white
+        }
+    }
+}
+
+impl Integrator for DirectLightingIntegrator {
+    fn ray_color(&self, r: Ray, depth: i32, ctx: &RenderContext) -> Color {
+        if depth <= 0 {
+            return black();
+        }
+        let hit = first_hit(r, ctx);
+        let along = transmittance(r, hit.as_ref(), &ctx.media, ctx);
+        let Some(mut rec) = hit else {
+            return element_wise_mul(along, ctx.background(&r));
+        };
+
+        let emitted = ctx.emitted(&rec);
+
+        let mut ctx = *ctx;
+        let ctx = &mut ctx;
+        let mut srec = ScatterRecord::new();
+        let mat = rec.mat;
+        let Some(weight) = scatter_at_wavelength(&r, &mut rec, &mut srec, ctx) else {
+            return element_wise_mul(along, emitted);
+        };
+
+        if srec.skip_pdf {
+            ctx.media = media_after(&rec, srec.skip_pdf_ray.direction(), ctx);
+            let attenuation = element_wise_mul(weight, ctx.factor(srec.attenuation));
+            let reflected = element_wise_mul(attenuation, self.ray_color(srec.skip_pdf_ray, depth - 1, ctx));
+            return element_wise_mul(along, emitted + reflected);
+        }
+
+        let heuristic = Heuristic::Power;
+        let from_lights = match sample_lights(&r, &rec, mat, &srec, ctx) {
+            Some((contribution, pdf_light, pdf_bsdf)) => contribution * heuristic.weight(pdf_light, pdf_bsdf),
+            None => black(),
+        } + illuminate_delta(&r, &rec, mat, &srec, ctx);
+
+        // A material sample, counting only the emission it runs into.
+        let mut from_material = black();
+        let scattered = Ray::with_time(rec.p, srec.pdf.generate(), r.time());
+        if !scattered.direction().near_zero() {
+            let pdf_bsdf = srec.pdf.value(scattered.direction());
+            let value = ctx.factor(mat.scattering_value(&r, &rec, &srec, &scattered));
+            if pdf_bsdf > 0.0 && !is_black(value) {
+                let hit = first_hit(scattered, ctx);
+                let pdf_light = light_pdf(scattered, hit.as_ref(), ctx);
+                let radiance = element_wise_mul(
+                    emitted_by(scattered, hit.as_ref(), ctx),
+                    transmittance(scattered, hit.as_ref(), &media_after(&rec, scattered.direction(), ctx), ctx),
+                );
+                from_material = element_wise_mul(value, radiance) * (heuristic.weight(pdf_bsdf, pdf_light) / pdf_bsdf);
+            }
+        }
+
+        element_wise_mul(along, emitted + element_wise_mul(weight, from_lights + from_material))
+    }
+}
+
+impl New for PathIntegrator {
+    fn new() -> Self {
+        Self { rr_depth: DEFAULT_RR_DEPTH }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::luminance;
+    use crate::material::{DiffuseLight, Glossy, Lambertian};
+    use crate::point3::Point3;
+    use crate::quad::Quad;
+    use crate::sphere::{Add, HittableObject, Sphere};
+    use crate::texture::Texture;
+
+    /// A floor, a glossy ball and a small, bright lamp above them, with
+    /// nothing else lighting the scene.
+    fn lamp_scene() -> (HittableList, Vec<Light>) {
+        let lamp = HittableObject::Sphere(Sphere {
+            center: Point3::new(0.5, 1.5, 0.0),
+            radius: 0.15,
+            mat: Material::DiffuseLight(DiffuseLight::from_texture(Texture::solid(Color::new(60.0, 60.0, 60.0)))),
+        });
+        let mut world = HittableList::new();
+        world.add(HittableObject::Quad(Quad::new(
+            Point3::new(-2.0, 0.0, -2.0),
+            Vec3::new(0.0, 0.0, 4.0),
+            Vec3::new(4.0, 0.0, 0.0),
+            Material::Lambertian(Lambertian::new(Color::new(0.6, 0.6, 0.6))),
+        )));
+        world.add(HittableObject::Sphere(Sphere {
+            center: Point3::new(-0.4, 0.4, 0.0),
+            radius: 0.4,
+            mat: Material::Glossy(Glossy::from_texture(Texture::solid(Color::new(0.8, 0.8, 0.8)), 0.3)),
+        }));
+        world.add(lamp.clone());
+        (world, vec![Light::Area(lamp)])
+    }
+
+    /// Mean luminance seen through a fan of rays at the floor and the ball.
+    fn mean_luminance(integrator: &IntegratorKind, ctx: &RenderContext, samples: usize) -> f32 {
+        let origin = Point3::new(0.0, 1.0, 3.0);
+        let mut sum = 0.0;
+        for i in 0..samples {
+            let target = Point3::new((i % 16) as f32 / 8.0 - 1.0, 0.3, ((i / 16) % 8) as f32 / 4.0 - 1.0);
+            let color = integrator.ray_color(Ray::new(origin, target - origin), 8, ctx);
+            sum += luminance(color);
+        }
+        sum / samples as f32
+    }
+
+    #[test]
+    fn mis_converges_to_the_path_traced_mean() {
+        let (world, lights) = lamp_scene();
+        let background = Background::Solid(Color::new(0.0, 0.0, 0.0));
+        let ctx = RenderContext {
+            world: &world,
+            lights: &lights,
+            delta_lights: &[],
+            background: &background,
+            spectral: None,
+            dispersed_wavelength: None,
+            media: MediumStack::new(),
+        };
+        let path = IntegratorKind::Path(PathIntegrator { rr_depth: DEFAULT_RR_DEPTH });
+        let samples = 200_000;
+        let expected = mean_luminance(&path, &ctx, samples);
+        assert!(expected > 0.05, "{}", expected);
+        for heuristic in [Heuristic::Balance, Heuristic::Power] {
+            let mis = IntegratorKind::Mis(MisIntegrator { heuristic, rr_depth: DEFAULT_RR_DEPTH });
+            let mean = mean_luminance(&mis, &ctx, samples);
+            assert!((mean - expected).abs() < 0.03 * expected, "MIS {} vs path {}", mean, expected);
+        }
+    }
+}
@@ -16,6 +16,10 @@ pub trait Clamp {
     fn clamp(&self, x: f32) -> f32;
 }
 
+pub trait Expand {
+    fn expand(&self, delta: f32) -> Self;
+}
+
 pub struct Interval {
     pub min: f32,
     pub max: f32,
@@ -39,16 +43,27 @@ impl Surrounds for Interval {
     }
 }
 
+impl Expand for Interval {
+    fn expand(&self, delta: f32) -> Self {
+        let padding = delta / 2.0;
+        Interval::new(self.min - padding, self.max + padding)
+    }
+}
+
 impl Interval {
     pub fn new(min: f32, max: f32) -> Self {
         Self { min, max }
     }
+
+    /// The tightest interval enclosing both `a` and `b`.
+    pub fn enclosing(a: Interval, b: Interval) -> Self {
+        Self { min: a.min.min(b.min), max: a.max.max(b.max) }
+    }
 }
- 
 
 impl Interval {
-    const EMPTY: Interval = Interval { min: INF, max: -INF };
-    const UNIVERSE: Interval = Interval { min: -INF, max: INF };
+    pub const EMPTY: Interval = Interval { min: INF, max: -INF };
+    pub const UNIVERSE: Interval = Interval { min: -INF, max: INF };
 }
 
 impl Clamp for Interval {
@@ -61,4 +76,12 @@ impl Clamp for Interval {
             x
         }
     }
-}
\ No newline at end of file
+}
+
+impl Copy for Interval {}
+
+impl Clone for Interval {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
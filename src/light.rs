@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::environment::EnvironmentMap;
+use crate::interval::Interval;
+use crate::point3::Point3;
+use crate::punctual::{DirectionalLight, Illuminate, PointLight, SpotLight};
+use crate::ray::Ray;
+use crate::sky::Sky;
+use crate::sphere::{Hit, HitRecord, HittableObject, New};
+use crate::vec3::Vec3;
+
+/// Shapes that can be sampled by direction as seen from a point, so they can
+/// be lit explicitly instead of waiting for a scattered ray to find them.
+pub trait SampleLight {
+    /// Solid-angle density of `random(origin)` producing `direction`, or 0
+    /// when the direction misses the shape.
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f32;
+
+    /// A direction from `origin` towards a random point on the shape. Not
+    /// necessarily unit length.
+    fn random(&self, origin: Point3) -> Vec3;
+}
+
+/// Something in the scene that emits light and can be sampled directly.
+#[derive(Clone)]
+pub enum Light {
+    /// An emissive sphere, triangle, quad or disk. The same object must also
+    /// be in the world so that rays can hit it.
+    Area(HittableObject),
+    /// The environment map, which must also be the camera's background.
+    Environment(Arc<EnvironmentMap>),
+    /// The sun and sky, which must also be the camera's background.
+    Sky(Arc<Sky>),
+    Point(PointLight),
+    Spot(SpotLight),
+    Directional(DirectionalLight),
+}
+
+impl Light {
+    /// Whether the light is a single point or direction. These can't be
+    /// sampled by density, only illuminate points through shadow rays.
+    pub fn is_delta(&self) -> bool {
+        matches!(self, Light::Point(_) | Light::Spot(_) | Light::Directional(_))
+    }
+}
+
+impl SampleLight for Light {
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f32 {
+        match self {
+            Light::Area(object) => object.pdf_value(origin, direction),
+            Light::Environment(map) => map.pdf_value(origin, direction),
+            Light::Sky(sky) => sky.pdf_value(origin, direction),
+            Light::Point(_) | Light::Spot(_) | Light::Directional(_) => 0.0,
+        }
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        match self {
+            Light::Area(object) => object.random(origin),
+            Light::Environment(map) => map.random(origin),
+            Light::Sky(sky) => sky.random(origin),
+            Light::Point(light) => light.position - origin,
+            Light::Spot(light) => light.position - origin,
+            Light::Directional(light) => -light.direction,
+        }
+    }
+}
+
+impl Illuminate for Light {
+    fn illuminate(&self, p: Point3) -> Option<(Vec3, f32, Color)> {
+        match self {
+            Light::Point(light) => light.illuminate(p),
+            Light::Spot(light) => light.illuminate(p),
+            Light::Directional(light) => light.illuminate(p),
+            Light::Area(_) | Light::Environment(_) | Light::Sky(_) => None,
+        }
+    }
+}
+
+impl Light {
+    /// Whether this light is what `r` runs into first, given `hit`, the
+    /// first hit in the world or `None` when the ray escapes. Light sampled
+    /// from one light only counts when it really comes from that light.
+    pub fn is_first_hit(&self, r: Ray, hit: Option<&HitRecord>) -> bool {
+        match (self, hit) {
+            (Light::Area(object), Some(hit)) => {
+                let mut rec = HitRecord::new();
+                object.hit(r, Interval::new(0.001, hit.t * (1.0 + 1e-4)), &mut rec)
+            }
+            (Light::Environment(_) | Light::Sky(_), None) => true,
+            _ => false,
+        }
+    }
+}
@@ -1,11 +1,11 @@
-use camera::{Camera, Initialize, Render};
-use indicatif::ProgressBar;
-use interval::Interval;
-use point3::Point3;
-use ray::At;
-use rtweekend::{random_double_range, INF};
-use sphere::{Add, Hit, HitRecord, HittableList, New, Sphere};
-use vec3::{dot, element_wise_mul, random, random_range, Length, LengthSquared, Unit, Vec3};
+use std::{env, path::Path, process};
+
+use bvh::Bvh;
+use camera::{Camera, Render};
+use rtweekend::random_double_range;
+use scene::Scene;
+use sphere::{Add, HittableList, New, Sphere};
+use vec3::{element_wise_mul, random, random_range, Length, Vec3};
 mod vec3;
 pub mod color;
 mod ray;
@@ -15,22 +15,95 @@ mod rtweekend;
 mod interval;
 mod camera;
 mod material;
+mod aabb;
+mod bvh;
+mod triangle;
+mod mesh;
+mod obj;
+mod scene;
+mod film;
+mod quad;
+mod light;
+mod texture;
+mod perlin;
+mod image;
+mod transform;
+mod instance;
+mod medium;
+mod plane;
+mod pdf;
+mod integrator;
+mod environment;
+mod sky;
+mod punctual;
+mod microfacet;
+mod spectrum;
+mod dispersion;
+
+const USAGE: &str = "usage: pobert [-o OUTPUT.{png,ppm,pfm}] [--spectral] [SCENE.toml]";
 
 fn main() {
+    // Render the scene file given on the command line, or the book cover scene
+    // when there is none.
+    let mut scene_path = None;
+    let mut output = String::from("image.png");
+    let mut spectral = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => match args.next() {
+                Some(path) => output = path,
+                None => fail(USAGE),
+            },
+            "--spectral" => spectral = true,
+            "-h" | "--help" => {
+                eprintln!("{}", USAGE);
+                return;
+            }
+            _ if scene_path.is_none() && !arg.starts_with('-') => scene_path = Some(arg),
+            _ => fail(USAGE),
+        }
+    }
+
+    // Catch a bad output path before spending time on the render.
+    if let Err(e) = film::ImageFormat::from_path(Path::new(&output)) {
+        fail(&e.to_string());
+    }
+
+    let scene = match scene_path {
+        Some(path) => scene::load_scene(&path).unwrap_or_else(|e| fail(&e.to_string())),
+        None => book_cover(),
+    };
+
+    let mut world = HittableList::new();
+    world.add(sphere::HittableObject::Bvh(Bvh::new(scene.world)));
+
+    let mut cam = scene.camera;
+    cam.spectral |= spectral;
+    let film = cam.render(&world, &scene.lights);
+    if let Err(e) = film.write(&output) {
+        fail(&format!("could not write {}: {}", output, e));
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1);
+}
+
+/// The final scene from "Ray Tracing in One Weekend": a grid of small random
+/// spheres around three large ones.
+fn book_cover() -> Scene {
     // World
 
     let mut world = HittableList::new(); 
 
     let material_ground = material::Material::Lambertian(material::Lambertian::new(Vec3::new(0.5, 0.5, 0.5)));
-    world.add(sphere::HittableObject::Sphere(Sphere {
-        center: point3::Point3 {
-            x: 0.0,
-            y: -1000.0,
-            z: 0.0,
-        },
-        radius: 1000.0,
-        mat: material_ground,
-    }));
+    world.add(sphere::HittableObject::Plane(plane::Plane::new(
+        point3::Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        material_ground,
+    )));
 
     for a in -11..11 {
         for b in -11..11 {
@@ -137,8 +210,6 @@ fn main() {
 
     cam.defocus_angle = 0.6;
     cam.focus_dist = 10.0;
-    
-
-    cam.render(&world)
 
+    Scene { world, lights: Vec::new(), camera: cam }
 }
@@ -1,67 +1,238 @@
-use crate::{color::Color, ray::{Direction, Ray}, rtweekend::random_double, sphere::HitRecord, vec3::{random_unit_vector, reflect, refract, Dot, NearZero, Unit, Vec3}};
+use crate::{color::{luminance, Color}, dispersion::{Dispersion, D_LINE}, microfacet::{dielectric_value, fresnel_conductor, fresnel_dielectric, fresnel_schlick, ggx_d, gtr1_d, smith_g2}, pdf::{CosinePdf, GgxPdf, PhongPdf, Pdf, PrincipledPdf, RoughDielectricPdf, SamplePdf, SpherePdf}, point3::Point3, ray::{Direction, Ray, Time}, rtweekend::{random_double, PI}, sphere::{HitRecord, New}, texture::{Texture, Value}, vec3::{random_unit_vector, reflect, refract, Dot, Onb, Unit, Vec3}};
 
+#[derive(Clone)]
 pub enum Material {
     Lambertian(Lambertian),
     Metal(Metal),
+    Glossy(Glossy),
     Dialectric(Dialectric),
+    DiffuseLight(DiffuseLight),
+    Isotropic(Isotropic),
+    Conductor(Conductor),
+    Principled(Principled),
     // Add other material types here as needed
 }
 
+#[derive(Clone)]
 pub struct Metal {
-    pub albedo: Color,
+    pub albedo: Texture,
     pub fuzz: f32,
 }
 
+/// Blurry reflection spread over a Phong lobe around the mirror direction,
+/// which the integrator samples and evaluates like any other density, so it
+/// takes part in light sampling where `Metal`'s fuzz can't.
+#[derive(Clone)]
+pub struct Glossy {
+    pub albedo: Texture,
+    pub fuzz: f32,
+}
+
+#[derive(Clone)]
 pub struct Lambertian {
-    pub albedo: Color,
+    pub albedo: Texture,
 }
 
+/// Glass and other clear materials. Smooth at zero `roughness`; above it,
+/// frosted, with GGX microfacets spreading both reflection and refraction.
 pub struct Dialectric {
+    /// Index of refraction, at the d line for dispersive materials.
+    pub ior: f32,
+    pub roughness: f32,
+    /// How the index of refraction varies with wavelength, for glass that
+    /// splits light into colors. `None` keeps `ior` at every wavelength.
+    pub dispersion: Option<Dispersion>,
+    /// Absorption coefficient σ of each channel: after a distance `d`
+    /// inside, `exp(-σ d)` of the light is left, following the Beer–Lambert
+    /// law. Zero for clear glass. The integrator charges it along the way,
+    /// which needs the shape to be closed.
+    pub absorption: Color,
+}
+
+/// Metal as a GGX microfacet surface. Its color comes from the Fresnel
+/// reflectance of the complex index of refraction `eta + i k`, given per
+/// RGB channel. `roughness` runs from 0, a perfect mirror, to 1.
+pub struct Conductor {
+    pub eta: Color,
+    pub k: Color,
+    pub roughness: f32,
+}
+
+/// Measured metals for `Conductor::preset`.
+#[derive(Clone, Copy)]
+pub enum ConductorPreset {
+    Gold,
+    Copper,
+    Aluminium,
+    Silver,
+}
+
+/// One material for most surfaces, after Disney's principled BSDF. A
+/// diffuse base with sheen sits under a GGX specular layer that turns into
+/// metal as `metallic` goes to 1, or into rough glass as `transmission`
+/// does, and a clear coat can go on top. All parameters but `ior` run from
+/// 0 to 1.
+#[derive(Clone)]
+pub struct Principled {
+    pub base_color: Texture,
+    pub metallic: f32,
+    pub roughness: f32,
+    /// Strength of the highlight on non-metals; 0.5 reflects 4% of the
+    /// light at normal incidence.
+    pub specular: f32,
+    /// How far the highlight on non-metals takes on the base color.
+    pub specular_tint: f32,
+    /// Extra reflection at grazing angles, as on cloth.
+    pub sheen: f32,
+    pub clearcoat: f32,
+    /// Glossiness of the clear coat, from satin at 0 to gloss at 1.
+    pub clearcoat_gloss: f32,
+    pub transmission: f32,
     pub ior: f32,
 }
 
+/// Emits `emit` from both sides of the surface and doesn't scatter.
+#[derive(Clone)]
+pub struct DiffuseLight {
+    pub emit: Texture,
+}
+
+/// Phase function of a participating medium: scatters equally in every
+/// direction, tinted by `albedo`.
+#[derive(Clone)]
+pub struct Isotropic {
+    pub albedo: Texture,
+}
+
+/// Result of scattering a ray off a material. Diffuse materials describe the
+/// directions they scatter into with `pdf` and leave the choice to the
+/// integrator, which may mix in light sampling. Specular ones pick the
+/// outgoing ray themselves and set `skip_pdf`.
+pub struct ScatterRecord {
+    pub attenuation: Color,
+    pub pdf: Pdf<'static>,
+    pub skip_pdf: bool,
+    pub skip_pdf_ray: Ray,
+    /// Wavelength in nm to scatter at, set by the integrator.
+    pub wavelength: f32,
+    /// Set by materials whose scattering depends on `wavelength`, so that
+    /// the integrator keeps only light of that wavelength on the path.
+    pub dispersed: bool,
+}
+
 pub trait Scatter {
-    fn scatter(&self, r_in: &Ray, rec: &mut HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool;
+    fn scatter(&self, r_in: &Ray, rec: &mut HitRecord, srec: &mut ScatterRecord) -> bool;
+}
+
+/// Density of a material scattering `r_in` into the direction of `scattered`,
+/// i.e. its BSDF times the cosine term with the albedo factored out.
+pub trait ScatteringPdf {
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f32;
+}
+
+/// BSDF times the cosine term for light arriving along `scattered` and
+/// leaving back along `r_in`, given the record `scatter` filled in. For most
+/// materials that is `srec.attenuation` times `scattering_pdf`; those whose
+/// color changes with direction work it out in full.
+pub trait ScatteringValue {
+    fn scattering_value(&self, r_in: &Ray, rec: &HitRecord, srec: &ScatterRecord, scattered: &Ray) -> Color;
+}
+
+pub trait Emitted {
+    fn emitted(&self, u: f32, v: f32, p: Point3) -> Color;
+}
+
+/// The absorption coefficient of the medium enclosed by surfaces of
+/// materials that let light through into their inside, or `None` for those
+/// that don't.
+pub trait Interior {
+    fn interior(&self) -> Option<Color>;
 }
 
 impl Scatter for Material {
-    fn scatter(&self, r_in: &Ray, rec: &mut HitRecord, attenuation: &mut Color, scattered: &mut Ray) -> bool {
+    fn scatter(&self, r_in: &Ray, rec: &mut HitRecord, srec: &mut ScatterRecord) -> bool {
         match self {
-            Material::Lambertian(lambertian) => Lambertian::scatter(lambertian, r_in, rec, attenuation, scattered),
-            Material::Metal(metal) => Metal::scatter(metal, r_in, rec, attenuation, scattered),
-            Material::Dialectric(dialectric) => Dialectric::scatter(dialectric, r_in, rec, attenuation, scattered),
+            Material::Lambertian(lambertian) => Lambertian::scatter(lambertian, r_in, rec, srec),
+            Material::Metal(metal) => Metal::scatter(metal, r_in, rec, srec),
+            Material::Glossy(glossy) => Glossy::scatter(glossy, r_in, rec, srec),
+            Material::Dialectric(dialectric) => Dialectric::scatter(dialectric, r_in, rec, srec),
+            Material::DiffuseLight(_) => false,
+            Material::Isotropic(isotropic) => Isotropic::scatter(isotropic, r_in, rec, srec),
+            Material::Conductor(conductor) => Conductor::scatter(conductor, r_in, rec, srec),
+            Material::Principled(principled) => Principled::scatter(principled, r_in, rec, srec),
             // Add other material types here as needed
         }
     }
 }
 
-impl Copy for Material {}
+impl ScatteringPdf for Material {
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f32 {
+        match self {
+            Material::Lambertian(lambertian) => lambertian.scattering_pdf(r_in, rec, scattered),
+            Material::Glossy(glossy) => glossy.scattering_pdf(r_in, rec, scattered),
+            Material::Isotropic(isotropic) => isotropic.scattering_pdf(r_in, rec, scattered),
+            // Specular materials never go through the pdf.
+            _ => 0.0,
+        }
+    }
+}
 
-impl Clone for Material {
-    fn clone(&self) -> Self {
-        *self
+impl ScatteringValue for Material {
+    fn scattering_value(&self, r_in: &Ray, rec: &HitRecord, srec: &ScatterRecord, scattered: &Ray) -> Color {
+        match self {
+            Material::Conductor(conductor) => conductor.scattering_value(r_in, rec, srec, scattered),
+            Material::Principled(principled) => principled.scattering_value(r_in, rec, srec, scattered),
+            Material::Dialectric(dialectric) => dialectric.scattering_value(r_in, rec, srec, scattered),
+            _ => srec.attenuation * self.scattering_pdf(r_in, rec, scattered),
+        }
     }
 }
 
-impl Copy for Lambertian {}
+impl New for ScatterRecord {
+    fn new() -> Self {
+        Self {
+            attenuation: Color::new(0.0, 0.0, 0.0),
+            pdf: Pdf::Sphere(SpherePdf),
+            skip_pdf: false,
+            skip_pdf_ray: Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 0.0)),
+            wavelength: D_LINE,
+            dispersed: false,
+        }
+    }
+}
 
-impl Clone for Lambertian {
-    fn clone(&self) -> Self {
-        *self
+impl Emitted for Material {
+    fn emitted(&self, u: f32, v: f32, p: Point3) -> Color {
+        match self {
+            Material::DiffuseLight(light) => light.emitted(u, v, p),
+            _ => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+impl Interior for Material {
+    fn interior(&self) -> Option<Color> {
+        match self {
+            Material::Dialectric(dialectric) => Some(dialectric.absorption),
+            Material::Principled(principled) if principled.metallic < 1.0 && principled.transmission > 0.0 => {
+                Some(Color::new(0.0, 0.0, 0.0))
+            }
+            _ => None,
+        }
     }
 }
 
-impl Copy for Metal {}
+impl Copy for Dialectric {}
 
-impl Clone for Metal {
+impl Clone for Dialectric {
     fn clone(&self) -> Self {
         *self
     }
 }
 
-impl Copy for Dialectric {}
+impl Copy for Conductor {}
 
-impl Clone for Dialectric {
+impl Clone for Conductor {
     fn clone(&self) -> Self {
         *self
     }
@@ -69,73 +240,359 @@ impl Clone for Dialectric {
 
 impl Lambertian {
     pub fn new(a: Color) -> Self {
-        Self { albedo: a }
+        Self { albedo: Texture::solid(a) }
+    }
+
+    pub fn from_texture(tex: Texture) -> Self {
+        Self { albedo: tex }
     }
 }
 
 impl Scatter for Lambertian {
-    fn scatter(&self, r_in: &Ray, rec: &mut HitRecord, mut attenuation: &mut Vec3, mut scattered: &mut Ray) -> bool {
-        let scatter_direction: Vec3;
-        if (rec.normal + random_unit_vector()).near_zero() {
-            scatter_direction = rec.normal;
-        }
-        else {
-            scatter_direction = rec.normal + random_unit_vector();
-        }
-        let new_ray = Ray::new(rec.p, scatter_direction);
-        *scattered = new_ray.clone();
-        *attenuation = self.albedo.clone();
-        // eprintln!("Lambertian scatter: attenuation = {:?}, scattered = {:?}", attenuation, scattered);
+    fn scatter(&self, _r_in: &Ray, rec: &mut HitRecord, srec: &mut ScatterRecord) -> bool {
+        srec.attenuation = self.albedo.value(rec.u, rec.v, rec.p);
+        srec.pdf = Pdf::Cosine(CosinePdf::new(rec.normal));
+        srec.skip_pdf = false;
         true
     }
 }
 
+impl ScatteringPdf for Lambertian {
+    fn scattering_pdf(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f32 {
+        let cos_theta = rec.normal.dot(scattered.direction().unit());
+        (cos_theta / PI).max(0.0)
+    }
+}
+
 impl Scatter for Metal {
-    fn scatter(&self, r_in: &Ray, rec: &mut HitRecord, mut attenuation: &mut Color, mut scattered: &mut Ray) -> bool {
+    fn scatter(&self, r_in: &Ray, rec: &mut HitRecord, srec: &mut ScatterRecord) -> bool {
         let reflected = reflect(r_in.direction().unit(),rec.normal);
-        let new_ray = Ray::new(rec.p, reflected + random_unit_vector() * self.fuzz);
-        *scattered = new_ray.clone();
-        *attenuation = self.albedo.clone();
-        // eprintln!("Metal scatter: attenuation = {:?}, scattered = {:?}", attenuation, scattered);
-        scattered.direction().dot(rec.normal) > 0.0
+        srec.skip_pdf_ray = Ray::with_time(rec.p, reflected + random_unit_vector() * self.fuzz, r_in.time());
+        srec.attenuation = self.albedo.value(rec.u, rec.v, rec.p);
+        srec.skip_pdf = true;
+        srec.skip_pdf_ray.direction().dot(rec.normal) > 0.0
     }
 }
 
 impl Metal {
     pub fn new(a: Color, f: f32) -> Self {
-        Self { albedo: a, fuzz: f }
+        Self { albedo: Texture::solid(a), fuzz: f }
+    }
+
+    pub fn from_texture(tex: Texture, f: f32) -> Self {
+        Self { albedo: tex, fuzz: f }
+    }
+}
+
+impl Scatter for Glossy {
+    /// A perfect mirror when `fuzz` is zero, like `Metal`.
+    fn scatter(&self, r_in: &Ray, rec: &mut HitRecord, srec: &mut ScatterRecord) -> bool {
+        let reflected = reflect(r_in.direction().unit(), rec.normal);
+        srec.attenuation = self.albedo.value(rec.u, rec.v, rec.p);
+        if self.fuzz <= 0.0 {
+            srec.skip_pdf_ray = Ray::with_time(rec.p, reflected, r_in.time());
+            srec.skip_pdf = true;
+            return true
+        }
+        srec.pdf = Pdf::Phong(PhongPdf::new(reflected, self.exponent()));
+        srec.skip_pdf = false;
+        true
+    }
+}
+
+impl ScatteringPdf for Glossy {
+    /// The lobe's own density, cut off below the surface: directions that
+    /// would go into it are absorbed.
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> f32 {
+        if scattered.direction().dot(rec.normal) <= 0.0 {
+            return 0.0;
+        }
+        let reflected = reflect(r_in.direction().unit(), rec.normal);
+        PhongPdf::new(reflected, self.exponent()).value(scattered.direction())
+    }
+}
+
+impl Glossy {
+    pub fn from_texture(tex: Texture, fuzz: f32) -> Self {
+        Self { albedo: tex, fuzz }
+    }
+
+    /// Phong exponent of the lobe, from the same mapping of a Phong
+    /// exponent to fuzz as the OBJ loader uses for `Ns`.
+    fn exponent(&self) -> f32 {
+        (2.0 / (self.fuzz * self.fuzz) - 2.0).max(0.0)
     }
 }
 
 impl Dialectric {
     pub fn new(i: f32) -> Self {
-        Self { ior: i }
+        Self::rough(i, 0.0)
+    }
+
+    pub fn rough(ior: f32, roughness: f32) -> Self {
+        Self { ior, roughness, dispersion: None, absorption: Color::new(0.0, 0.0, 0.0) }
+    }
+
+    pub fn dispersive(dispersion: Dispersion, roughness: f32) -> Self {
+        Self { dispersion: Some(dispersion), ..Self::rough(dispersion.ior(D_LINE), roughness) }
+    }
+
+    /// Index on the far side of the surface over the one on the near side,
+    /// for light of `wavelength`.
+    fn eta(&self, rec: &HitRecord, wavelength: f32) -> f32 {
+        let ior = self.dispersion.map_or(self.ior, |d| d.ior(wavelength));
+        if rec.front_face { ior } else { 1.0 / ior }
+    }
+
+    fn alpha(&self) -> f32 {
+        self.roughness * self.roughness
     }
 }
 
 impl Scatter for Dialectric {
-    fn scatter(&self, r_in: &Ray, rec: &mut HitRecord, mut attenuation: &mut Color, mut scattered: &mut Ray) -> bool {
-        *attenuation = Color::new(1.0, 1.0, 1.0);
-        let refraction_ratio = if rec.front_face {1.0 / self.ior} else {self.ior};
+    /// Smooth glass picks between the mirror and the refracted direction
+    /// itself. Rough glass leaves sampling to the integrator, which reaches
+    /// the smooth case in the limit: the Fresnel term is the same exact one.
+    fn scatter(&self, r_in: &Ray, rec: &mut HitRecord, srec: &mut ScatterRecord) -> bool {
+        srec.attenuation = Color::new(1.0, 1.0, 1.0);
+        srec.dispersed = self.dispersion.is_some();
+        let eta = self.eta(rec, srec.wavelength);
         let unit_direction = r_in.direction().unit();
 
-        let cos_theta = (-unit_direction).dot(rec.normal).min(1.0);
-        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
-
-        let cannot_refract = refraction_ratio * sin_theta > 1.0;
+        if self.alpha() >= SMOOTH_ALPHA {
+            srec.pdf = Pdf::RoughDielectric(RoughDielectricPdf::new(rec.normal, -unit_direction, self.alpha(), eta));
+            srec.skip_pdf = false;
+            return true;
+        }
 
-        let direction = if cannot_refract || reflectance(cos_theta, refraction_ratio) > random_double() {
+        // Under total internal reflection the Fresnel term is 1.
+        let cos_theta = (-unit_direction).dot(rec.normal).min(1.0);
+        let direction = if fresnel_dielectric(cos_theta, eta) > random_double() {
             reflect(unit_direction, rec.normal)
         } else {
-            refract(unit_direction, rec.normal, refraction_ratio)
+            refract(unit_direction, rec.normal, 1.0 / eta)
         };
 
-        *scattered = Ray::new(rec.p, direction);
+        srec.skip_pdf_ray = Ray::with_time(rec.p, direction, r_in.time());
+        srec.skip_pdf = true;
         true
     }
 }
 
-fn reflectance(cosine: f32, ref_idx: f32) -> f32 {
-    let r0 = ((1.0-ref_idx) / (1.0+ref_idx)).powi(2);
-    r0 + (1.0-r0)*(1.0-cosine).powi(5)
-}
\ No newline at end of file
+impl ScatteringValue for Dialectric {
+    fn scattering_value(&self, r_in: &Ray, rec: &HitRecord, srec: &ScatterRecord, scattered: &Ray) -> Color {
+        let uvw = Onb::new(rec.normal);
+        let wo = uvw.local(-r_in.direction().unit());
+        let wi = uvw.local(scattered.direction().unit());
+        if wo.z <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let eta = self.eta(rec, srec.wavelength);
+        let value = dielectric_value(wo, wi, self.alpha(), eta);
+        Color::new(value, value, value)
+    }
+}
+
+impl DiffuseLight {
+    pub fn from_texture(tex: Texture) -> Self {
+        Self { emit: tex }
+    }
+}
+
+impl Emitted for DiffuseLight {
+    fn emitted(&self, u: f32, v: f32, p: Point3) -> Color {
+        self.emit.value(u, v, p)
+    }
+}
+
+impl Isotropic {
+    pub fn new(albedo: Color) -> Self {
+        Self { albedo: Texture::solid(albedo) }
+    }
+
+    pub fn from_texture(tex: Texture) -> Self {
+        Self { albedo: tex }
+    }
+}
+
+impl Scatter for Isotropic {
+    fn scatter(&self, _r_in: &Ray, rec: &mut HitRecord, srec: &mut ScatterRecord) -> bool {
+        srec.attenuation = self.albedo.value(rec.u, rec.v, rec.p);
+        srec.pdf = Pdf::Sphere(SpherePdf);
+        srec.skip_pdf = false;
+        true
+    }
+}
+
+impl ScatteringPdf for Isotropic {
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f32 {
+        1.0 / (4.0 * PI)
+    }
+}
+
+impl Conductor {
+    pub fn new(eta: Color, k: Color, roughness: f32) -> Self {
+        Self { eta, k, roughness }
+    }
+
+    pub fn preset(metal: ConductorPreset, roughness: f32) -> Self {
+        let (eta, k) = match metal {
+            ConductorPreset::Gold => (Color::new(0.143, 0.374, 1.442), Color::new(3.983, 2.385, 1.603)),
+            ConductorPreset::Copper => (Color::new(0.200, 0.924, 1.102), Color::new(3.912, 2.452, 2.142)),
+            ConductorPreset::Aluminium => (Color::new(1.657, 0.880, 0.521), Color::new(9.224, 6.270, 4.837)),
+            ConductorPreset::Silver => (Color::new(0.155, 0.117, 0.138), Color::new(4.828, 3.122, 2.147)),
+        };
+        Self::new(eta, k, roughness)
+    }
+
+    /// GGX width. Squaring the roughness spreads the visible change in
+    /// blur more evenly over its range.
+    fn alpha(&self) -> f32 {
+        self.roughness * self.roughness
+    }
+}
+
+/// Below this width a conductor is treated as a perfect mirror.
+const SMOOTH_ALPHA: f32 = 1e-3;
+
+impl Scatter for Conductor {
+    /// Smooth conductors reflect straight off as a mirror. Rough ones leave
+    /// sampling to the integrator, with reflections about the microfacets
+    /// visible from the incoming ray.
+    fn scatter(&self, r_in: &Ray, rec: &mut HitRecord, srec: &mut ScatterRecord) -> bool {
+        let unit_direction = r_in.direction().unit();
+        if self.alpha() < SMOOTH_ALPHA {
+            let cos_theta = (-unit_direction).dot(rec.normal);
+            srec.attenuation = fresnel_conductor(cos_theta, self.eta, self.k);
+            srec.skip_pdf_ray = Ray::with_time(rec.p, reflect(unit_direction, rec.normal), r_in.time());
+            srec.skip_pdf = true;
+            return true;
+        }
+        srec.attenuation = Color::new(1.0, 1.0, 1.0);
+        srec.pdf = Pdf::Ggx(GgxPdf::new(rec.normal, -unit_direction, self.alpha()));
+        srec.skip_pdf = false;
+        true
+    }
+}
+
+impl ScatteringValue for Conductor {
+    /// F D G / (4 cos(theta_o)): the cosine towards the light cancels the
+    /// one in the Torrance–Sparrow denominator.
+    fn scattering_value(&self, r_in: &Ray, rec: &HitRecord, _srec: &ScatterRecord, scattered: &Ray) -> Color {
+        let uvw = Onb::new(rec.normal);
+        let wo = uvw.local(-r_in.direction().unit());
+        let wi = uvw.local(scattered.direction().unit());
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return Color::new(0.0, 0.0, 0.0);
+        }
+        let h = (wo + wi).unit();
+        let fresnel = fresnel_conductor(wo.dot(h), self.eta, self.k);
+        let alpha = self.alpha();
+        fresnel * (ggx_d(h, alpha) * smith_g2(wo, wi, alpha) / (4.0 * wo.z))
+    }
+}
+
+impl Principled {
+    /// A rough white plastic, or `tex` colored.
+    pub fn from_texture(tex: Texture) -> Self {
+        Self {
+            base_color: tex,
+            metallic: 0.0,
+            roughness: 0.5,
+            specular: 0.5,
+            specular_tint: 0.0,
+            sheen: 0.0,
+            clearcoat: 0.0,
+            clearcoat_gloss: 1.0,
+            transmission: 0.0,
+            ior: 1.5,
+        }
+    }
+
+    fn alpha(&self) -> f32 {
+        (self.roughness * self.roughness).max(SMOOTH_ALPHA)
+    }
+
+    fn clearcoat_alpha(&self) -> f32 {
+        0.1 + (0.001 - 0.1) * self.clearcoat_gloss
+    }
+
+    /// Weights of the diffuse base, the specular layer and the glass.
+    fn weights(&self) -> (f32, f32, f32) {
+        let glass = (1.0 - self.metallic) * self.transmission;
+        let diffuse = (1.0 - self.metallic) * (1.0 - self.transmission);
+        (diffuse, 1.0 - glass, glass)
+    }
+}
+
+impl Scatter for Principled {
+    fn scatter(&self, r_in: &Ray, rec: &mut HitRecord, srec: &mut ScatterRecord) -> bool {
+        let (diffuse, specular, glass) = self.weights();
+        let lobes = [diffuse, specular, glass, 0.25 * self.clearcoat];
+        let eta = if rec.front_face { self.ior } else { 1.0 / self.ior };
+        srec.attenuation = self.base_color.value(rec.u, rec.v, rec.p);
+        srec.pdf = Pdf::Principled(PrincipledPdf::new(
+            rec.normal,
+            -r_in.direction(),
+            lobes,
+            self.alpha(),
+            self.clearcoat_alpha(),
+            eta,
+        ));
+        srec.skip_pdf = false;
+        true
+    }
+}
+
+impl ScatteringValue for Principled {
+    /// The base color comes from `srec.attenuation`, so the texture is only
+    /// looked up once per hit.
+    fn scattering_value(&self, r_in: &Ray, rec: &HitRecord, srec: &ScatterRecord, scattered: &Ray) -> Color {
+        let black = Color::new(0.0, 0.0, 0.0);
+        let white = Color::new(1.0, 1.0, 1.0);
+        let mix = |a: Color, b: Color, t: f32| a * (1.0 - t) + b * t;
+
+        let uvw = Onb::new(rec.normal);
+        let wo = uvw.local(-r_in.direction().unit());
+        let wi = uvw.local(scattered.direction().unit());
+        if wo.z <= 0.0 {
+            return black;
+        }
+        let base = srec.attenuation;
+        let (diffuse_weight, specular_weight, glass_weight) = self.weights();
+        let alpha = self.alpha();
+        let eta = if rec.front_face { self.ior } else { 1.0 / self.ior };
+
+        // Light coming through the glass picks up the base color; light
+        // reflected off it doesn't.
+        let glass = glass_weight * dielectric_value(wo, wi, alpha, eta);
+        if wi.z <= 0.0 {
+            return base * glass;
+        }
+        let mut value = white * glass;
+
+        let h = (wo + wi).unit();
+        let cos_d = wi.dot(h);
+        let schlick_weight = |cos: f32| (1.0 - cos).clamp(0.0, 1.0).powi(5);
+        let tint = if luminance(base) > 0.0 { base / luminance(base) } else { white };
+
+        // Diffuse, with the retro-reflection of rough surfaces at grazing
+        // angles, plus sheen.
+        let fd90 = 0.5 + 2.0 * self.roughness * cos_d * cos_d;
+        let fd = (1.0 + (fd90 - 1.0) * schlick_weight(wi.z)) * (1.0 + (fd90 - 1.0) * schlick_weight(wo.z));
+        let sheen = mix(white, tint, 0.5) * (self.sheen * schlick_weight(cos_d));
+        value = value + (base * (fd / PI) + sheen) * (diffuse_weight * wi.z);
+
+        let f0 = mix(mix(white, tint, self.specular_tint) * (0.08 * self.specular), base, self.metallic);
+        let microfacet = ggx_d(h, alpha) * smith_g2(wo, wi, alpha) / (4.0 * wo.z);
+        value = value + fresnel_schlick(cos_d, f0) * (specular_weight * microfacet);
+
+        if self.clearcoat > 0.0 {
+            let fresnel = fresnel_schlick(cos_d, Color::new(0.04, 0.04, 0.04)).x;
+            let clearcoat_alpha = self.clearcoat_alpha();
+            let coat = gtr1_d(h, clearcoat_alpha) * smith_g2(wo, wi, 0.25) / (4.0 * wo.z);
+            value = value + white * (0.25 * self.clearcoat * fresnel * coat);
+        }
+        value
+    }
+}
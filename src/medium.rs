@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use crate::aabb::{Aabb, BoundingBox};
+use crate::color::Color;
+use crate::interval::Interval;
+use crate::material::{Isotropic, Material};
+use crate::ray::{At, Direction, Ray};
+use crate::rtweekend::{random_double, INF};
+use crate::sphere::{Hit, HitRecord, HittableObject, New};
+use crate::texture::Texture;
+use crate::vec3::{Length, Vec3};
+
+/// Deepest nesting of clear media, like ice in water in a glass, that a
+/// path keeps track of.
+const MAX_NESTING: usize = 4;
+
+/// The clear media a path is inside, such as the glass and water of a
+/// filled glass, each given by its absorption coefficient, innermost last.
+/// Light travelling through the innermost one is absorbed following the
+/// Beer–Lambert law.
+#[derive(Clone, Copy)]
+pub struct MediumStack {
+    absorption: [Color; MAX_NESTING],
+    len: usize,
+}
+
+/// Fog or smoke of uniform density filling a closed, convex boundary shape.
+/// A ray passing through scatters at an exponentially distributed distance,
+/// or passes straight through when that distance lies beyond the far side.
+#[derive(Clone)]
+pub struct ConstantMedium {
+    boundary: Arc<HittableObject>,
+    neg_inv_density: f32,
+    phase_function: Material,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Arc<HittableObject>, density: f32, albedo: Texture) -> Self {
+        Self {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function: Material::Isotropic(Isotropic::from_texture(albedo)),
+        }
+    }
+}
+
+impl MediumStack {
+    /// Outside of every medium.
+    pub fn new() -> Self {
+        Self { absorption: [Color::new(0.0, 0.0, 0.0); MAX_NESTING], len: 0 }
+    }
+
+    /// The media after the path goes through the surface of one with
+    /// `absorption`, into it when `entering` and out of it otherwise.
+    /// Leaving a medium the path was never in, as through open geometry
+    /// from behind, changes nothing, and so does entering one deeper than
+    /// `MAX_NESTING`.
+    pub fn crossed(mut self, absorption: Color, entering: bool) -> Self {
+        if entering {
+            if self.len < MAX_NESTING {
+                self.absorption[self.len] = absorption;
+                self.len += 1;
+            }
+            return self;
+        }
+        let same = |a: &Color| a.x == absorption.x && a.y == absorption.y && a.z == absorption.z;
+        if let Some(i) = self.absorption[..self.len].iter().rposition(same) {
+            self.absorption.copy_within(i + 1..self.len, i);
+            self.len -= 1;
+        }
+        self
+    }
+
+    /// Share of each channel left after travelling `distance` through the
+    /// innermost medium: `exp(-absorption * distance)`.
+    pub fn transmittance(&self, distance: f32) -> Color {
+        let Some(a) = self.len.checked_sub(1).map(|i| self.absorption[i]) else {
+            return Color::new(1.0, 1.0, 1.0);
+        };
+        // Checked first so that a clear channel stays clear all the way
+        // to infinity.
+        let channel = |a: f32| if a > 0.0 { (-a * distance).exp() } else { 1.0 };
+        Color::new(channel(a.x), channel(a.y), channel(a.z))
+    }
+}
+
+impl Hit for ConstantMedium {
+    fn hit<'a>(&'a self, r: Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        // Find where the ray's line enters and leaves the boundary, even if
+        // the ray starts inside it.
+        let mut rec1 = HitRecord::new();
+        let mut rec2 = HitRecord::new();
+        if !self.boundary.hit(r, Interval::UNIVERSE, &mut rec1) {
+            return false;
+        }
+        if !self.boundary.hit(r, Interval::new(rec1.t + 0.0001, INF), &mut rec2) {
+            return false;
+        }
+
+        let t_enter = rec1.t.max(ray_t.min).max(0.0);
+        let t_exit = rec2.t.min(ray_t.max);
+        if t_enter >= t_exit {
+            return false;
+        }
+
+        let ray_length = r.direction().length();
+        let distance_inside_boundary = (t_exit - t_enter) * ray_length;
+        let hit_distance = self.neg_inv_density * random_double().ln();
+        if hit_distance > distance_inside_boundary {
+            return false;
+        }
+
+        rec.t = t_enter + hit_distance / ray_length;
+        rec.p = r.at(rec.t);
+        // Scattering inside a volume has no surface, so these are arbitrary.
+        rec.normal = Vec3::new(1.0, 0.0, 0.0);
+        rec.front_face = true;
+        rec.u = 0.0;
+        rec.v = 0.0;
+        rec.mat = &self.phase_function;
+        true
+    }
+}
+
+impl BoundingBox for ConstantMedium {
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
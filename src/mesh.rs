@@ -0,0 +1,115 @@
+use std::sync::Arc;
+
+use crate::aabb::{Aabb, BoundingBox};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::point3::Point3;
+use crate::ray::{At, Ray};
+use crate::sphere::{Hit, HitRecord, HittableObject, SetFaceNormal};
+use crate::triangle;
+use crate::vec3::{cross, Dot, Unit, Vec3};
+
+/// Indexed triangle mesh. Vertex positions, normals and texture coordinates
+/// live in separate buffers that faces index into independently, so a
+/// position can be shared by faces with different normals (hard edges) or UVs
+/// (texture seams).
+pub struct Mesh {
+    pub positions: Vec<Point3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<[f32; 2]>,
+    pub faces: Vec<MeshFace>,
+    pub mat: Material,
+}
+
+pub struct MeshFace {
+    pub positions: [usize; 3],
+    /// Per-vertex normals for smooth shading. Faces without them are flat.
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+}
+
+/// One face of a shared `Mesh`. Each triangle is a separate object so the
+/// BVH can split meshes at triangle granularity.
+#[derive(Clone)]
+pub struct MeshTriangle {
+    mesh: Arc<Mesh>,
+    face: usize,
+}
+
+impl Mesh {
+    pub fn new(positions: Vec<Point3>, faces: Vec<MeshFace>, mat: Material) -> Self {
+        Self { positions, normals: Vec::new(), uvs: Vec::new(), faces, mat }
+    }
+
+    /// Splits the mesh into one hittable per face, all sharing this mesh's buffers.
+    pub fn triangles(mesh: Arc<Mesh>) -> Vec<HittableObject> {
+        (0..mesh.faces.len())
+            .map(|face| HittableObject::MeshTriangle(MeshTriangle { mesh: mesh.clone(), face }))
+            .collect()
+    }
+}
+
+impl MeshFace {
+    pub fn new(positions: [usize; 3]) -> Self {
+        Self { positions, normals: None, uvs: None }
+    }
+}
+
+impl MeshTriangle {
+    fn vertices(&self) -> (Point3, Point3, Point3) {
+        let [i0, i1, i2] = self.mesh.faces[self.face].positions;
+        let p = &self.mesh.positions;
+        (p[i0], p[i1], p[i2])
+    }
+}
+
+impl Hit for MeshTriangle {
+    fn hit<'a>(&'a self, r: Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        let (v0, v1, v2) = self.vertices();
+        let Some(hit) = triangle::intersect(&r, v0, v1, v2, ray_t) else {
+            return false;
+        };
+
+        let face = &self.mesh.faces[self.face];
+        let b0 = 1.0 - hit.b1 - hit.b2;
+
+        rec.t = hit.t;
+        rec.p = r.at(hit.t);
+
+        match face.uvs {
+            Some([t0, t1, t2]) => {
+                let uvs = &self.mesh.uvs;
+                rec.u = b0 * uvs[t0][0] + hit.b1 * uvs[t1][0] + hit.b2 * uvs[t2][0];
+                rec.v = b0 * uvs[t0][1] + hit.b1 * uvs[t1][1] + hit.b2 * uvs[t2][1];
+            }
+            None => {
+                rec.u = hit.b1;
+                rec.v = hit.b2;
+            }
+        }
+
+        let geometric_normal = cross(v1 - v0, v2 - v0).unit();
+        rec.set_face_normal(r, geometric_normal);
+
+        if let Some([n0, n1, n2]) = face.normals {
+            let normals = &self.mesh.normals;
+            let mut shading_normal = (normals[n0] * b0 + normals[n1] * hit.b1 + normals[n2] * hit.b2).unit();
+            // Keep the interpolated normal on the same side as the geometry,
+            // then flip it the same way set_face_normal flipped the geometric one.
+            if shading_normal.dot(geometric_normal) < 0.0 {
+                shading_normal = -shading_normal;
+            }
+            rec.normal = if rec.front_face { shading_normal } else { -shading_normal };
+        }
+
+        rec.mat = &self.mesh.mat;
+        true
+    }
+}
+
+impl BoundingBox for MeshTriangle {
+    fn bounding_box(&self) -> Aabb {
+        let (v0, v1, v2) = self.vertices();
+        Aabb::enclosing(Aabb::from_points(v0, v1), Aabb::from_points(v0, v2))
+    }
+}
@@ -0,0 +1,327 @@
+use crate::color::Color;
+use crate::rtweekend::PI;
+use crate::vec3::{cross, Dot, Unit, Vec3};
+
+// GGX (Trowbridge–Reitz) microfacet distribution and the matching Smith
+// masking-shadowing. Directions are in a local frame with the surface normal
+// along +z, and `alpha` is the distribution's width: 0 for a mirror, 1 for a
+// very rough surface.
+
+/// Density of microfacet normals `h` per unit area of the macro surface.
+pub fn ggx_d(h: Vec3, alpha: f32) -> f32 {
+    if h.z <= 0.0 {
+        return 0.0;
+    }
+    let a2 = alpha * alpha;
+    let t = (a2 - 1.0) * h.z * h.z + 1.0;
+    a2 / (PI * t * t)
+}
+
+/// Stand-in for Smith's lambda at grazing angles, large enough to make the
+/// masking terms vanish.
+const INFINITE_LAMBDA: f32 = 1e8;
+
+/// Smith's auxiliary function: the projected area of microfacets facing away
+/// from `w` relative to those facing it.
+fn smith_lambda(w: Vec3, alpha: f32) -> f32 {
+    let cos2 = w.z * w.z;
+    if cos2 <= 0.0 {
+        return INFINITE_LAMBDA;
+    }
+    let tan2 = (1.0 - cos2).max(0.0) / cos2;
+    ((1.0 + alpha * alpha * tan2).sqrt() - 1.0) / 2.0
+}
+
+/// Fraction of microfacets visible from `w`.
+pub fn smith_g1(w: Vec3, alpha: f32) -> f32 {
+    1.0 / (1.0 + smith_lambda(w, alpha))
+}
+
+/// Fraction of microfacets visible from both `wo` and `wi`, with the
+/// correlation between the two that comes from microfacet height.
+pub fn smith_g2(wo: Vec3, wi: Vec3, alpha: f32) -> f32 {
+    1.0 / (1.0 + smith_lambda(wo, alpha) + smith_lambda(wi, alpha))
+}
+
+/// A microfacet normal drawn from those visible from `wo`, using two
+/// uniform random numbers (Heitz 2018). `wo` must be above the surface.
+pub fn sample_vndf(wo: Vec3, alpha: f32, u1: f32, u2: f32) -> Vec3 {
+    // Stretch the view direction so the distribution becomes a hemisphere.
+    let vh = Vec3::new(alpha * wo.x, alpha * wo.y, wo.z).unit();
+    let len2 = vh.x * vh.x + vh.y * vh.y;
+    let t1 = if len2 > 0.0 { Vec3::new(-vh.y, vh.x, 0.0) / len2.sqrt() } else { Vec3::new(1.0, 0.0, 0.0) };
+    let t2 = cross(vh, t1);
+
+    // A point on the disk, squeezed towards the part of the hemisphere
+    // that faces the viewer.
+    let r = u1.sqrt();
+    let phi = 2.0 * PI * u2;
+    let p1 = r * phi.cos();
+    let s = 0.5 * (1.0 + vh.z);
+    let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+    let p3 = (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+    let nh = t1 * p1 + t2 * p2 + vh * p3;
+
+    // Unstretch back to the ellipsoid.
+    Vec3::new(alpha * nh.x, alpha * nh.y, nh.z.max(0.0)).unit()
+}
+
+/// Density of `sample_vndf` producing `h`.
+pub fn vndf_pdf(wo: Vec3, h: Vec3, alpha: f32) -> f32 {
+    if wo.z <= 0.0 {
+        return 0.0;
+    }
+    smith_g1(wo, alpha) * wo.dot(h).max(0.0) * ggx_d(h, alpha) / wo.z
+}
+
+/// Unpolarized Fresnel reflectance of a conductor with complex index of
+/// refraction `eta + i k`, per channel, at `cos_theta` from the normal.
+pub fn fresnel_conductor(cos_theta: f32, eta: Color, k: Color) -> Color {
+    let channel = |eta: f32, k: f32| {
+        let cos2 = cos_theta.clamp(0.0, 1.0).powi(2);
+        let sin2 = 1.0 - cos2;
+        let t0 = eta * eta - k * k - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+
+        let t1 = a2_plus_b2 + cos2;
+        let t2 = 2.0 * a * cos2.sqrt();
+        let rs = (t1 - t2) / (t1 + t2);
+
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+
+        0.5 * (rs + rp)
+    };
+    Color::new(channel(eta.x, k.x), channel(eta.y, k.y), channel(eta.z, k.z))
+}
+
+/// Fresnel reflectance of a dielectric interface for light arriving at
+/// `cos_theta_i` from the normal, where `eta` is the index of refraction on
+/// the far side over the one on the near side.
+pub fn fresnel_dielectric(cos_theta_i: f32, eta: f32) -> f32 {
+    let cos_i = cos_theta_i.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let r_parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (r_parallel * r_parallel + r_perpendicular * r_perpendicular) / 2.0
+}
+
+/// Schlick's approximation to the Fresnel reflectance, with normal
+/// incidence reflectance `f0` per channel.
+pub fn fresnel_schlick(cos_theta: f32, f0: Color) -> Color {
+    let t = (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5);
+    f0 + (Color::new(1.0, 1.0, 1.0) - f0) * t
+}
+
+/// Direction `wo` takes through an interface with normal `n` on its side,
+/// or `None` under total internal reflection. Both point away from the
+/// surface, so the result is on the far side.
+fn refract_about(wo: Vec3, n: Vec3, eta: f32) -> Option<Vec3> {
+    let cos_i = wo.dot(n);
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-wo / eta + n * (cos_i / eta - cos_t))
+}
+
+/// Microfacet normal between `wo` and `wi` for a dielectric with relative
+/// index `eta`, facing up, or `None` when no microfacet can scatter the one
+/// into the other.
+fn dielectric_half_vector(wo: Vec3, wi: Vec3, eta: f32) -> Option<Vec3> {
+    let reflect = wi.z > 0.0;
+    let h = if reflect { wo + wi } else { wo + wi * eta };
+    if wi.z == 0.0 || h.x == 0.0 && h.y == 0.0 && h.z == 0.0 {
+        return None;
+    }
+    let h = h.unit();
+    let h = if h.z < 0.0 { -h } else { h };
+    // Microfacets seen from behind by either direction don't count.
+    let front = wo.dot(h) > 0.0 && if reflect { wi.dot(h) > 0.0 } else { wi.dot(h) < 0.0 };
+    front.then_some(h)
+}
+
+/// BSDF times |cos(theta_i)| of a rough dielectric interface (Walter et
+/// al. 2007), for light arriving along `wi` either side of the surface and
+/// leaving along `wo`, which must be above it. `eta` is the index below
+/// over the index above.
+pub fn dielectric_value(wo: Vec3, wi: Vec3, alpha: f32, eta: f32) -> f32 {
+    let Some(h) = dielectric_half_vector(wo, wi, eta) else {
+        return 0.0;
+    };
+    let fresnel = fresnel_dielectric(wo.dot(h), eta);
+    let d = ggx_d(h, alpha);
+    let g = smith_g2(wo, wi, alpha);
+    if wi.z > 0.0 {
+        return fresnel * d * g / (4.0 * wo.z);
+    }
+    // Radiance is squeezed into a narrower cone on the denser side, hence
+    // the division by eta squared.
+    let denom = wi.dot(h) + wo.dot(h) / eta;
+    (1.0 - fresnel) * d * g * (wi.dot(h) * wo.dot(h)).abs() / (denom * denom * wo.z * eta * eta)
+}
+
+/// Density of `sample_dielectric` producing `wi`.
+pub fn dielectric_pdf(wo: Vec3, wi: Vec3, alpha: f32, eta: f32) -> f32 {
+    let Some(h) = dielectric_half_vector(wo, wi, eta) else {
+        return 0.0;
+    };
+    let fresnel = fresnel_dielectric(wo.dot(h), eta);
+    let pdf_h = vndf_pdf(wo, h, alpha);
+    if wi.z > 0.0 {
+        return fresnel * pdf_h / (4.0 * wo.dot(h));
+    }
+    let denom = wi.dot(h) + wo.dot(h) / eta;
+    (1.0 - fresnel) * pdf_h * wi.dot(h).abs() / (denom * denom)
+}
+
+/// Reflects or refracts `wo` off a visible microfacet, choosing between
+/// the two by the Fresnel reflectance. Takes three uniform random numbers.
+/// `None` when the microfacet sends the light to the wrong side of the
+/// surface, a reflection below it or a refraction above it, in which case
+/// the light is lost.
+pub fn sample_dielectric(wo: Vec3, alpha: f32, eta: f32, u1: f32, u2: f32, u3: f32) -> Option<Vec3> {
+    let h = sample_vndf(wo, alpha, u1, u2);
+    if u3 < fresnel_dielectric(wo.dot(h), eta) {
+        let reflected = h * (2.0 * wo.dot(h)) - wo;
+        return (reflected.z > 0.0).then_some(reflected);
+    }
+    refract_about(wo, h, eta).filter(|refracted| refracted.z < 0.0)
+}
+
+/// The "generalized Trowbridge–Reitz" distribution with exponent 1, whose
+/// long tails give clearcoat its haze.
+pub fn gtr1_d(h: Vec3, alpha: f32) -> f32 {
+    if h.z <= 0.0 {
+        return 0.0;
+    }
+    let a2 = alpha * alpha;
+    (a2 - 1.0) / (PI * a2.ln() * (1.0 + (a2 - 1.0) * h.z * h.z))
+}
+
+/// A microfacet normal with density `gtr1_d(h) * h.z`.
+pub fn sample_gtr1(alpha: f32, u1: f32, u2: f32) -> Vec3 {
+    let a2 = alpha * alpha;
+    let cos_theta = ((1.0 - a2.powf(1.0 - u1)) / (1.0 - a2)).max(0.0).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * u2;
+    Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, cos_theta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::{GgxPdf, SamplePdf};
+    use crate::rtweekend::random_double;
+
+    const SAMPLES: usize = 200_000;
+
+    /// Midpoint-rule integral of `f` over the directions with z in
+    /// `[z_min, 1]`. Cells even in z and azimuth all cover the same solid
+    /// angle, and a fixed grid keeps peaked lobes from making the tests flaky.
+    fn integrate_above(z_min: f32, f: impl Fn(Vec3) -> f32) -> f32 {
+        const STEPS_Z: usize = 1000;
+        const STEPS_PHI: usize = 200;
+        let mut sum = 0.0;
+        for i in 0..STEPS_Z {
+            let z = z_min + (1.0 - z_min) * (i as f32 + 0.5) / STEPS_Z as f32;
+            let r = (1.0 - z * z).max(0.0).sqrt();
+            for j in 0..STEPS_PHI {
+                let phi = 2.0 * PI * (j as f32 + 0.5) / STEPS_PHI as f32;
+                sum += f(Vec3::new(r * phi.cos(), r * phi.sin(), z));
+            }
+        }
+        sum * (1.0 - z_min) * 2.0 * PI / (STEPS_Z * STEPS_PHI) as f32
+    }
+
+    fn integrate_hemisphere(f: impl Fn(Vec3) -> f32) -> f32 {
+        integrate_above(0.0, f)
+    }
+
+    fn integrate_sphere(f: impl Fn(Vec3) -> f32) -> f32 {
+        integrate_above(-1.0, f)
+    }
+
+    fn direction_at(theta_degrees: f32) -> Vec3 {
+        let theta = theta_degrees.to_radians();
+        Vec3::new(theta.sin(), 0.0, theta.cos())
+    }
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!((actual - expected).abs() <= tolerance, "{} vs {}", actual, expected);
+    }
+
+    #[test]
+    fn projected_ggx_normals_cover_the_surface_once() {
+        for alpha in [0.2, 0.5, 1.0] {
+            assert_close(integrate_hemisphere(|h| ggx_d(h, alpha) * h.z), 1.0, 0.03);
+        }
+    }
+
+    #[test]
+    fn vndf_pdf_integrates_to_one() {
+        for (alpha, theta) in [(0.3, 0.0), (0.3, 60.0), (0.8, 80.0)] {
+            let wo = direction_at(theta);
+            assert_close(integrate_hemisphere(|h| vndf_pdf(wo, h, alpha)), 1.0, 0.03);
+        }
+    }
+
+    #[test]
+    fn vndf_samples_follow_vndf_pdf() {
+        // The mean of a function of the sampled normal has to match its
+        // integral against the density.
+        let (alpha, wo) = (0.4, direction_at(50.0));
+        let sampled: f32 = (0..SAMPLES).map(|_| sample_vndf(wo, alpha, random_double(), random_double()).x).sum::<f32>() / SAMPLES as f32;
+        let expected = integrate_hemisphere(|h| h.x * vndf_pdf(wo, h, alpha));
+        assert_close(sampled, expected, 0.01);
+    }
+
+    #[test]
+    fn ggx_pdf_matches_the_reflections_it_generates() {
+        // Reflections that end up below the surface aren't counted by
+        // `value`, so it integrates to the share of those above.
+        let (alpha, wo) = (0.5, direction_at(70.0));
+        let pdf = GgxPdf::new(Vec3::new(0.0, 0.0, 1.0), wo, alpha);
+        let above = (0..SAMPLES).filter(|_| pdf.generate().z > 0.0).count() as f32 / SAMPLES as f32;
+        assert!(above < 0.99);
+        assert_close(integrate_hemisphere(|wi| pdf.value(wi)), above, 0.03);
+    }
+
+    #[test]
+    fn dielectric_pdf_matches_the_directions_sampled() {
+        // Samples that come out on the wrong side are lost, and the density
+        // integrates to the share of those that aren't.
+        for (alpha, theta, eta) in [(0.3, 40.0, 1.5), (0.6, 75.0, 1.5), (0.3, 30.0, 1.0 / 1.5)] {
+            let wo = direction_at(theta);
+            let kept = (0..SAMPLES)
+                .filter(|_| sample_dielectric(wo, alpha, eta, random_double(), random_double(), random_double()).is_some())
+                .count() as f32
+                / SAMPLES as f32;
+            assert_close(integrate_sphere(|wi| dielectric_pdf(wo, wi, alpha, eta)), kept, 0.03);
+        }
+    }
+
+    #[test]
+    fn dielectric_weight_is_the_masking_ratio() {
+        // With the visible normals sampled, value / pdf leaves G2 / G1, and
+        // for refraction also the 1 / eta² of radiance entering a denser
+        // medium.
+        let (alpha, wo, eta) = (0.4, direction_at(35.0), 1.5);
+        for _ in 0..1000 {
+            let Some(wi) = sample_dielectric(wo, alpha, eta, random_double(), random_double(), random_double()) else {
+                continue;
+            };
+            let weight = dielectric_value(wo, wi, alpha, eta) / dielectric_pdf(wo, wi, alpha, eta);
+            let masking = smith_g2(wo, wi, alpha) / smith_g1(wo, alpha);
+            let expected = if wi.z > 0.0 { masking } else { masking / (eta * eta) };
+            assert_close(weight, expected, 1e-3 * expected.max(1.0));
+        }
+    }
+}
@@ -0,0 +1,456 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::image::Image;
+use crate::material::{Dialectric, Lambertian, Material, Metal};
+use crate::mesh::{Mesh, MeshFace};
+use crate::point3::Point3;
+use crate::sphere::HittableObject;
+use crate::texture::{ImageTexture, Texture};
+use crate::vec3::Vec3;
+
+/// Problem found while loading an `.obj` or `.mtl` file. `line` is 1-based,
+/// or 0 when the file couldn't be read at all.
+#[derive(Debug)]
+pub struct ObjError {
+    pub path: PathBuf,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}: {}", self.path.display(), self.message)
+        } else {
+            write!(f, "{}:{}: {}", self.path.display(), self.line, self.message)
+        }
+    }
+}
+
+impl Error for ObjError {}
+
+/// Contents of an OBJ file, one entry per `g`/`o` group in file order.
+pub struct ObjModel {
+    pub groups: Vec<ObjGroup>,
+}
+
+pub struct ObjGroup {
+    pub objects: Vec<HittableObject>,
+}
+
+impl ObjModel {
+    /// Flattens all groups into a single list of triangles.
+    pub fn into_objects(self) -> Vec<HittableObject> {
+        self.groups.into_iter().flat_map(|g| g.objects).collect()
+    }
+}
+
+// Used for faces that appear before any `usemtl`.
+fn default_material() -> Material {
+    Material::Lambertian(Lambertian::new(Color::new(0.8, 0.8, 0.8)))
+}
+
+/// Indices of one face corner into the file-wide position, UV and normal lists.
+#[derive(Clone, Copy)]
+struct Corner {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+/// Faces of one group that share a material.
+struct FaceSet {
+    group: usize,
+    material: Material,
+    triangles: Vec<[Corner; 3]>,
+}
+
+struct Parser {
+    path: PathBuf,
+    line: usize,
+}
+
+impl Parser {
+    fn error(&self, message: impl Into<String>) -> ObjError {
+        ObjError { path: self.path.clone(), line: self.line, message: message.into() }
+    }
+
+    fn float(&self, token: Option<&str>, what: &str) -> Result<f32, ObjError> {
+        let token = token.ok_or_else(|| self.error(format!("missing {}", what)))?;
+        token.parse().map_err(|_| self.error(format!("expected a number for {}, found '{}'", what, token)))
+    }
+
+    fn vec3(&self, tokens: &mut std::str::SplitWhitespace, what: &str) -> Result<Vec3, ObjError> {
+        let x = self.float(tokens.next(), what)?;
+        let y = self.float(tokens.next(), what)?;
+        let z = self.float(tokens.next(), what)?;
+        Ok(Vec3::new(x, y, z))
+    }
+
+    /// Resolves a 1-based (or negative, relative) OBJ index against a list of `len` items.
+    fn index(&self, token: &str, len: usize, what: &str) -> Result<usize, ObjError> {
+        let i: i64 = token
+            .parse()
+            .map_err(|_| self.error(format!("expected a {} index, found '{}'", what, token)))?;
+        let resolved = if i > 0 { i - 1 } else { len as i64 + i };
+        if i == 0 || resolved < 0 || resolved >= len as i64 {
+            return Err(self.error(format!("{} index {} out of range ({} defined so far)", what, i, len)));
+        }
+        Ok(resolved as usize)
+    }
+
+    fn corner(&self, token: &str, positions: usize, uvs: usize, normals: usize) -> Result<Corner, ObjError> {
+        let mut parts = token.split('/');
+        let position = self.index(parts.next().unwrap_or(""), positions, "vertex")?;
+        let uv = match parts.next() {
+            Some("") | None => None,
+            Some(t) => Some(self.index(t, uvs, "texture coordinate")?),
+        };
+        let normal = match parts.next() {
+            Some("") | None => None,
+            Some(t) => Some(self.index(t, normals, "normal")?),
+        };
+        if parts.next().is_some() {
+            return Err(self.error(format!("malformed face vertex '{}'", token)));
+        }
+        Ok(Corner { position, uv, normal })
+    }
+}
+
+fn read(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|e| ObjError { path: path.to_path_buf(), line: 0, message: e.to_string() })
+}
+
+/// Loads an OBJ file and any MTL libraries it references. Polygons with more
+/// than three vertices are fan-triangulated, so they are assumed convex.
+pub fn load_obj(path: impl AsRef<Path>) -> Result<ObjModel, ObjError> {
+    let path = path.as_ref();
+    let source = read(path)?;
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+
+    let mut parser = Parser { path: path.to_path_buf(), line: 0 };
+    let mut positions: Vec<Point3> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<[f32; 2]> = Vec::new();
+    let mut materials: HashMap<String, Material> = HashMap::new();
+
+    let mut group_count = 1;
+    let mut current_group = 0;
+    let mut current_material = default_material();
+    let mut current_set: Option<usize> = None;
+    let mut face_sets: Vec<FaceSet> = Vec::new();
+
+    for (number, raw_line) in source.lines().enumerate() {
+        parser.line = number + 1;
+        let line = raw_line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        match keyword {
+            "v" => positions.push(parser.vec3(&mut tokens, "vertex position")?),
+            "vn" => normals.push(parser.vec3(&mut tokens, "vertex normal")?),
+            "vt" => {
+                let u = parser.float(tokens.next(), "texture coordinate")?;
+                let v = match tokens.next() {
+                    Some(t) => parser.float(Some(t), "texture coordinate")?,
+                    None => 0.0,
+                };
+                uvs.push([u, v]);
+            }
+            "f" => {
+                let corners = tokens
+                    .map(|t| parser.corner(t, positions.len(), uvs.len(), normals.len()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if corners.len() < 3 {
+                    return Err(parser.error(format!("face needs at least 3 vertices, found {}", corners.len())));
+                }
+                let set = *current_set.get_or_insert_with(|| {
+                    face_sets.push(FaceSet { group: current_group, material: current_material.clone(), triangles: Vec::new() });
+                    face_sets.len() - 1
+                });
+                for i in 1..corners.len() - 1 {
+                    face_sets[set].triangles.push([corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            "g" | "o" => {
+                current_group = group_count;
+                group_count += 1;
+                current_set = None;
+            }
+            "usemtl" => {
+                let name = tokens.next().ok_or_else(|| parser.error("usemtl needs a material name"))?;
+                current_material = materials
+                    .get(name)
+                    .ok_or_else(|| parser.error(format!("unknown material '{}'", name)))?
+                    .clone();
+                current_set = None;
+            }
+            "mtllib" => {
+                let names: Vec<&str> = tokens.collect();
+                if names.is_empty() {
+                    return Err(parser.error("mtllib needs a file name"));
+                }
+                for name in names {
+                    let mtl_path = base_dir.join(name);
+                    if !mtl_path.is_file() {
+                        return Err(parser.error(format!("material library '{}' not found", mtl_path.display())));
+                    }
+                    materials.extend(load_mtl(&mtl_path)?);
+                }
+            }
+            // Smoothing groups, lines, points and free-form geometry aren't supported.
+            _ => {}
+        }
+    }
+
+    let mut groups: Vec<ObjGroup> = (0..group_count).map(|_| ObjGroup { objects: Vec::new() }).collect();
+    for set in face_sets {
+        let mesh = build_mesh(&set, &positions, &normals, &uvs);
+        groups[set.group].objects.extend(Mesh::triangles(Arc::new(mesh)));
+    }
+    groups.retain(|g| !g.objects.is_empty());
+
+    Ok(ObjModel { groups })
+}
+
+/// Builds a mesh holding only the vertex data referenced by `set`, remapping
+/// the file-wide indices to mesh-local ones.
+fn build_mesh(set: &FaceSet, positions: &[Point3], normals: &[Vec3], uvs: &[[f32; 2]]) -> Mesh {
+    let mut position_map = HashMap::new();
+    let mut normal_map = HashMap::new();
+    let mut uv_map = HashMap::new();
+    let mut mesh = Mesh::new(Vec::new(), Vec::new(), set.material.clone());
+
+    for triangle in &set.triangles {
+        let mut face = MeshFace::new([0; 3]);
+        let mut face_normals = [0; 3];
+        let mut face_uvs = [0; 3];
+        for (k, corner) in triangle.iter().enumerate() {
+            face.positions[k] = *position_map.entry(corner.position).or_insert_with(|| {
+                mesh.positions.push(positions[corner.position]);
+                mesh.positions.len() - 1
+            });
+            if let Some(n) = corner.normal {
+                face_normals[k] = *normal_map.entry(n).or_insert_with(|| {
+                    mesh.normals.push(normals[n]);
+                    mesh.normals.len() - 1
+                });
+            }
+            if let Some(t) = corner.uv {
+                face_uvs[k] = *uv_map.entry(t).or_insert_with(|| {
+                    mesh.uvs.push(uvs[t]);
+                    mesh.uvs.len() - 1
+                });
+            }
+        }
+        // Attributes are only used when every corner of the face has them.
+        if triangle.iter().all(|c| c.normal.is_some()) {
+            face.normals = Some(face_normals);
+        }
+        if triangle.iter().all(|c| c.uv.is_some()) {
+            face.uvs = Some(face_uvs);
+        }
+        mesh.faces.push(face);
+    }
+    mesh
+}
+
+/// Raw MTL parameters before they are mapped onto one of our materials.
+struct MtlParams {
+    diffuse: Color,
+    diffuse_map: Option<Texture>,
+    specular: Color,
+    shininess: f32,
+    ior: f32,
+    dissolve: f32,
+    illum: u32,
+}
+
+impl MtlParams {
+    fn new() -> Self {
+        Self {
+            diffuse: Color::new(0.8, 0.8, 0.8),
+            diffuse_map: None,
+            specular: Color::new(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            ior: 1.5,
+            dissolve: 1.0,
+            illum: 2,
+        }
+    }
+
+    /// Transparent materials (dissolve below 1, or a refraction illum model)
+    /// become dielectrics; mirror-like ones (illum 3, or specular without
+    /// diffuse) become metals with fuzz derived from the Phong exponent;
+    /// everything else is Lambertian, textured by `map_Kd` if present.
+    fn to_material(&self) -> Material {
+        let is_black = |c: Color| c.x <= 0.0 && c.y <= 0.0 && c.z <= 0.0;
+        if self.dissolve < 1.0 || matches!(self.illum, 4 | 6 | 7 | 9) {
+            Material::Dialectric(Dialectric::new(self.ior))
+        } else if self.illum == 3 || (is_black(self.diffuse) && !is_black(self.specular)) {
+            let fuzz = (2.0 / (self.shininess + 2.0)).sqrt().min(1.0);
+            Material::Metal(Metal::new(self.specular, fuzz))
+        } else if let Some(map) = &self.diffuse_map {
+            Material::Lambertian(Lambertian::from_texture(map.clone()))
+        } else {
+            Material::Lambertian(Lambertian::new(self.diffuse))
+        }
+    }
+}
+
+fn load_mtl(path: &Path) -> Result<HashMap<String, Material>, ObjError> {
+    let source = read(path)?;
+    let mut parser = Parser { path: path.to_path_buf(), line: 0 };
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlParams)> = None;
+
+    for (number, raw_line) in source.lines().enumerate() {
+        parser.line = number + 1;
+        let line = raw_line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+
+        if keyword == "newmtl" {
+            let name = tokens.next().ok_or_else(|| parser.error("newmtl needs a material name"))?;
+            if let Some((name, params)) = current.take() {
+                materials.insert(name, params.to_material());
+            }
+            current = Some((name.to_string(), MtlParams::new()));
+            continue;
+        }
+
+        let Some((_, params)) = current.as_mut() else {
+            if matches!(keyword, "Kd" | "Ks" | "Ns" | "Ni" | "d" | "Tr" | "illum" | "map_Kd") {
+                return Err(parser.error(format!("'{}' before any newmtl", keyword)));
+            }
+            continue;
+        };
+
+        match keyword {
+            "Kd" => params.diffuse = parser.vec3(&mut tokens, "diffuse color")?,
+            "Ks" => params.specular = parser.vec3(&mut tokens, "specular color")?,
+            "Ns" => params.shininess = parser.float(tokens.next(), "specular exponent")?,
+            "Ni" => {
+                let ior = parser.float(tokens.next(), "index of refraction")?;
+                if ior <= 0.0 {
+                    return Err(parser.error(format!("index of refraction must be positive, found {}", ior)));
+                }
+                params.ior = ior;
+            }
+            "d" => params.dissolve = parser.float(tokens.next(), "dissolve")?,
+            "Tr" => params.dissolve = 1.0 - parser.float(tokens.next(), "transparency")?,
+            "illum" => {
+                let token = tokens.next().ok_or_else(|| parser.error("missing illumination model"))?;
+                params.illum = token
+                    .parse()
+                    .map_err(|_| parser.error(format!("expected an illumination model, found '{}'", token)))?;
+            }
+            "map_Kd" => {
+                // Options such as `-s` or `-o` come before the file name; they're ignored.
+                let name = tokens.last().ok_or_else(|| parser.error("map_Kd needs a file name"))?;
+                let image = Image::load(path.parent().unwrap_or(Path::new("")).join(name))
+                    .map_err(|e| parser.error(e.to_string()))?;
+                params.diffuse_map = Some(Texture::Image(ImageTexture::new(image)));
+            }
+            // Other texture maps and parameters aren't supported yet.
+            _ => {}
+        }
+    }
+
+    if let Some((name, params)) = current {
+        materials.insert(name, params.to_material());
+    }
+    Ok(materials)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interval::Interval;
+    use crate::ray::Ray;
+    use crate::rtweekend::INF;
+    use crate::sphere::{Hit, HitRecord, New};
+
+    /// Writes `files` into a fresh directory of their own and returns it.
+    fn write_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pobert-obj-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        for (file, contents) in files {
+            fs::write(dir.join(file), contents).unwrap();
+        }
+        dir
+    }
+
+    fn nearest_hit(objects: &[HittableObject], r: Ray) -> Option<f32> {
+        let mut rec = HitRecord::new();
+        let mut closest = None;
+        for object in objects {
+            if object.hit(r, Interval::new(0.001, closest.unwrap_or(INF)), &mut rec) {
+                closest = Some(rec.t);
+            }
+        }
+        closest
+    }
+
+    #[test]
+    fn splits_polygons_into_triangles_by_group() {
+        let dir = write_files(
+            "groups",
+            &[(
+                "model.obj",
+                "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+                 g quad\nf 1 2 3 4\n\
+                 g triangle\nf -4 -3 -2 # relative to the end\n",
+            )],
+        );
+        let model = load_obj(dir.join("model.obj")).unwrap();
+        let counts: Vec<usize> = model.groups.iter().map(|g| g.objects.len()).collect();
+        assert_eq!(counts, [2, 1]);
+
+        let objects = model.into_objects();
+        let down = Vec3::new(0.0, 0.0, -1.0);
+        assert_eq!(nearest_hit(&objects, Ray::new(Point3::new(0.25, 0.75, 1.0), down)), Some(1.0));
+        assert_eq!(nearest_hit(&objects, Ray::new(Point3::new(1.5, 0.5, 1.0), down)), None);
+    }
+
+    #[test]
+    fn reports_the_line_of_a_bad_index() {
+        let dir = write_files("index", &[("model.obj", "v 0 0 0\nv 1 0 0\n\nf 1 2 3\n")]);
+        let error = load_obj(dir.join("model.obj")).err().unwrap();
+        assert_eq!(error.line, 4);
+        assert!(error.message.contains("vertex index 3 out of range"), "{}", error.message);
+    }
+
+    #[test]
+    fn rejects_a_non_positive_ior_in_the_material_library() {
+        let dir = write_files(
+            "ior",
+            &[
+                ("model.obj", "mtllib glass.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl glass\nf 1 2 3\n"),
+                ("glass.mtl", "newmtl glass\nd 0.5\nNi 0\n"),
+            ],
+        );
+        let error = load_obj(dir.join("model.obj")).err().unwrap();
+        assert_eq!(error.path, dir.join("glass.mtl"));
+        assert_eq!(error.line, 3);
+    }
+
+    #[test]
+    fn maps_mtl_parameters_onto_materials() {
+        let glass = MtlParams { dissolve: 0.5, ior: 1.33, ..MtlParams::new() };
+        assert!(matches!(glass.to_material(), Material::Dialectric(d) if d.ior == 1.33));
+        let mirror = MtlParams { illum: 3, specular: Color::new(0.9, 0.9, 0.9), ..MtlParams::new() };
+        assert!(matches!(mirror.to_material(), Material::Metal(_)));
+        assert!(matches!(MtlParams::new().to_material(), Material::Lambertian(_)));
+    }
+}
@@ -0,0 +1,305 @@
+use crate::light::{Light, SampleLight};
+use crate::microfacet::{dielectric_pdf, gtr1_d, sample_dielectric, sample_gtr1, sample_vndf, vndf_pdf};
+use crate::point3::Point3;
+use crate::rtweekend::{random_double, PI};
+use crate::vec3::{random_cosine_direction, random_unit_vector, Dot, Onb, Unit, Vec3};
+
+/// A probability density over directions, which can both be sampled and be
+/// evaluated for a direction sampled some other way.
+pub trait SamplePdf {
+    /// Solid-angle density of `generate` producing `direction`.
+    fn value(&self, direction: Vec3) -> f32;
+
+    /// A random direction distributed according to this density. Not
+    /// necessarily unit length, and zero when the sample is lost, as when a
+    /// microfacet would send it to the wrong side of the surface.
+    fn generate(&self) -> Vec3;
+}
+
+pub enum Pdf<'a> {
+    Cosine(CosinePdf),
+    Sphere(SpherePdf),
+    Phong(PhongPdf),
+    Ggx(GgxPdf),
+    Principled(PrincipledPdf),
+    RoughDielectric(RoughDielectricPdf),
+    Light(LightPdf<'a>),
+    Mixture(MixturePdf<'a>),
+}
+
+/// Cosine-weighted hemisphere around a surface normal, which matches the
+/// Lambertian BRDF times the cosine term exactly.
+pub struct CosinePdf {
+    uvw: Onb,
+}
+
+/// Uniform over all directions.
+pub struct SpherePdf;
+
+/// Phong lobe around a mirror direction: density proportional to
+/// cos(alpha)^exponent, alpha being the angle from `uvw.w`.
+pub struct PhongPdf {
+    uvw: Onb,
+    exponent: f32,
+}
+
+/// Reflections off GGX microfacets visible from `wo`, the direction towards
+/// the viewer. `wo` is stored in the local frame of `uvw`, whose `w` is the
+/// surface normal.
+pub struct GgxPdf {
+    uvw: Onb,
+    wo: Vec3,
+    alpha: f32,
+}
+
+/// Reflection or refraction through GGX microfacets visible from `wo`,
+/// chosen by their Fresnel reflectance. The frame and `wo` are as for
+/// `GgxPdf`; `eta` is the index behind the surface over the one in front.
+pub struct RoughDielectricPdf {
+    uvw: Onb,
+    wo: Vec3,
+    alpha: f32,
+    eta: f32,
+}
+
+/// The lobes of the principled material, each picked with the chance given
+/// in `lobes`: diffuse, specular, glass and clearcoat. The frame and `wo`
+/// are as for `GgxPdf`; `eta` is the index behind the surface over the one
+/// in front of it.
+pub struct PrincipledPdf {
+    uvw: Onb,
+    wo: Vec3,
+    lobes: [f32; 4],
+    alpha: f32,
+    clearcoat_alpha: f32,
+    eta: f32,
+}
+
+/// Directions towards the scene's lights as seen from `origin`, with every
+/// light equally likely to be picked.
+pub struct LightPdf<'a> {
+    lights: &'a [Light],
+    origin: Point3,
+}
+
+/// Even mix of two densities: each sample comes from one of them chosen at
+/// random, and the density is their average.
+pub struct MixturePdf<'a> {
+    p0: &'a Pdf<'a>,
+    p1: &'a Pdf<'a>,
+}
+
+impl SamplePdf for Pdf<'_> {
+    fn value(&self, direction: Vec3) -> f32 {
+        match self {
+            Pdf::Cosine(pdf) => pdf.value(direction),
+            Pdf::Sphere(pdf) => pdf.value(direction),
+            Pdf::Phong(pdf) => pdf.value(direction),
+            Pdf::Ggx(pdf) => pdf.value(direction),
+            Pdf::Principled(pdf) => pdf.value(direction),
+            Pdf::RoughDielectric(pdf) => pdf.value(direction),
+            Pdf::Light(pdf) => pdf.value(direction),
+            Pdf::Mixture(pdf) => pdf.value(direction),
+        }
+    }
+
+    fn generate(&self) -> Vec3 {
+        match self {
+            Pdf::Cosine(pdf) => pdf.generate(),
+            Pdf::Sphere(pdf) => pdf.generate(),
+            Pdf::Phong(pdf) => pdf.generate(),
+            Pdf::Ggx(pdf) => pdf.generate(),
+            Pdf::Principled(pdf) => pdf.generate(),
+            Pdf::RoughDielectric(pdf) => pdf.generate(),
+            Pdf::Light(pdf) => pdf.generate(),
+            Pdf::Mixture(pdf) => pdf.generate(),
+        }
+    }
+}
+
+impl CosinePdf {
+    pub fn new(normal: Vec3) -> Self {
+        Self { uvw: Onb::new(normal) }
+    }
+}
+
+impl SamplePdf for CosinePdf {
+    fn value(&self, direction: Vec3) -> f32 {
+        let cosine_theta = direction.unit().dot(self.uvw.w);
+        (cosine_theta / PI).max(0.0)
+    }
+
+    fn generate(&self) -> Vec3 {
+        self.uvw.transform(random_cosine_direction())
+    }
+}
+
+impl SamplePdf for SpherePdf {
+    fn value(&self, _direction: Vec3) -> f32 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn generate(&self) -> Vec3 {
+        random_unit_vector()
+    }
+}
+
+impl PhongPdf {
+    pub fn new(axis: Vec3, exponent: f32) -> Self {
+        Self { uvw: Onb::new(axis), exponent }
+    }
+}
+
+impl SamplePdf for PhongPdf {
+    fn value(&self, direction: Vec3) -> f32 {
+        let cos_alpha = direction.unit().dot(self.uvw.w);
+        if cos_alpha <= 0.0 {
+            return 0.0;
+        }
+        (self.exponent + 1.0) / (2.0 * PI) * cos_alpha.powf(self.exponent)
+    }
+
+    fn generate(&self) -> Vec3 {
+        let cos_alpha = random_double().powf(1.0 / (self.exponent + 1.0));
+        let sin_alpha = (1.0 - cos_alpha * cos_alpha).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_double();
+        self.uvw.transform(Vec3::new(phi.cos() * sin_alpha, phi.sin() * sin_alpha, cos_alpha))
+    }
+}
+
+impl GgxPdf {
+    pub fn new(normal: Vec3, wo: Vec3, alpha: f32) -> Self {
+        let uvw = Onb::new(normal);
+        Self { uvw, wo: uvw.local(wo.unit()), alpha }
+    }
+}
+
+impl SamplePdf for GgxPdf {
+    /// The density of the microfacet normal, divided by the Jacobian of
+    /// reflecting about it.
+    fn value(&self, direction: Vec3) -> f32 {
+        let wi = self.uvw.local(direction.unit());
+        if wi.z <= 0.0 {
+            return 0.0;
+        }
+        let h = (self.wo + wi).unit();
+        if self.wo.dot(h) <= 0.0 {
+            return 0.0;
+        }
+        vndf_pdf(self.wo, h, self.alpha) / (4.0 * self.wo.dot(h))
+    }
+
+    fn generate(&self) -> Vec3 {
+        let h = sample_vndf(self.wo, self.alpha, random_double(), random_double());
+        self.uvw.transform(h * (2.0 * self.wo.dot(h)) - self.wo)
+    }
+}
+
+impl RoughDielectricPdf {
+    pub fn new(normal: Vec3, wo: Vec3, alpha: f32, eta: f32) -> Self {
+        let uvw = Onb::new(normal);
+        Self { uvw, wo: uvw.local(wo.unit()), alpha, eta }
+    }
+}
+
+impl SamplePdf for RoughDielectricPdf {
+    fn value(&self, direction: Vec3) -> f32 {
+        dielectric_pdf(self.wo, self.uvw.local(direction.unit()), self.alpha, self.eta)
+    }
+
+    fn generate(&self) -> Vec3 {
+        let wi = sample_dielectric(self.wo, self.alpha, self.eta, random_double(), random_double(), random_double());
+        wi.map_or(Vec3::new(0.0, 0.0, 0.0), |wi| self.uvw.transform(wi))
+    }
+}
+
+impl PrincipledPdf {
+    /// `lobes` needn't add up to one.
+    pub fn new(normal: Vec3, wo: Vec3, lobes: [f32; 4], alpha: f32, clearcoat_alpha: f32, eta: f32) -> Self {
+        let uvw = Onb::new(normal);
+        let total: f32 = lobes.iter().sum();
+        let lobes = lobes.map(|w| w / total);
+        Self { uvw, wo: uvw.local(wo.unit()), lobes, alpha, clearcoat_alpha, eta }
+    }
+}
+
+impl SamplePdf for PrincipledPdf {
+    fn value(&self, direction: Vec3) -> f32 {
+        let wo = self.wo;
+        let wi = self.uvw.local(direction.unit());
+        let [diffuse, specular, glass, clearcoat] = self.lobes;
+
+        let mut pdf = glass * dielectric_pdf(wo, wi, self.alpha, self.eta);
+        let h = (wo + wi).unit();
+        if wi.z > 0.0 && wo.dot(h) > 0.0 {
+            pdf += diffuse * wi.z / PI;
+            pdf += specular * vndf_pdf(wo, h, self.alpha) / (4.0 * wo.dot(h));
+            pdf += clearcoat * gtr1_d(h, self.clearcoat_alpha) * h.z / (4.0 * wo.dot(h));
+        }
+        pdf
+    }
+
+    fn generate(&self) -> Vec3 {
+        let wo = self.wo;
+        let [diffuse, specular, glass, _] = self.lobes;
+        let choice = random_double();
+        // Reflections that would go below the surface are lost, so that
+        // `value` is the density of what is left.
+        let wi = if choice < diffuse {
+            Some(random_cosine_direction())
+        } else if choice < diffuse + specular {
+            let h = sample_vndf(wo, self.alpha, random_double(), random_double());
+            Some(h * (2.0 * wo.dot(h)) - wo).filter(|wi| wi.z > 0.0)
+        } else if choice < diffuse + specular + glass {
+            sample_dielectric(wo, self.alpha, self.eta, random_double(), random_double(), random_double())
+        } else {
+            let h = sample_gtr1(self.clearcoat_alpha, random_double(), random_double());
+            Some(h * (2.0 * wo.dot(h)) - wo).filter(|wi| wi.z > 0.0)
+        };
+        wi.map_or(Vec3::new(0.0, 0.0, 0.0), |wi| self.uvw.transform(wi))
+    }
+}
+
+impl<'a> LightPdf<'a> {
+    /// `lights` must not be empty.
+    pub fn new(lights: &'a [Light], origin: Point3) -> Self {
+        Self { lights, origin }
+    }
+
+    /// One of the lights, picked uniformly at random.
+    pub fn choose(&self) -> &'a Light {
+        let index = ((random_double() * self.lights.len() as f32) as usize).min(self.lights.len() - 1);
+        &self.lights[index]
+    }
+}
+
+impl SamplePdf for LightPdf<'_> {
+    fn value(&self, direction: Vec3) -> f32 {
+        let sum: f32 = self.lights.iter().map(|light| light.pdf_value(self.origin, direction)).sum();
+        sum / self.lights.len() as f32
+    }
+
+    fn generate(&self) -> Vec3 {
+        self.choose().random(self.origin)
+    }
+}
+
+impl<'a> MixturePdf<'a> {
+    pub fn new(p0: &'a Pdf<'a>, p1: &'a Pdf<'a>) -> Self {
+        Self { p0, p1 }
+    }
+}
+
+impl SamplePdf for MixturePdf<'_> {
+    fn value(&self, direction: Vec3) -> f32 {
+        0.5 * self.p0.value(direction) + 0.5 * self.p1.value(direction)
+    }
+
+    fn generate(&self) -> Vec3 {
+        if random_double() < 0.5 {
+            self.p0.generate()
+        } else {
+            self.p1.generate()
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use crate::aabb::{Aabb, BoundingBox};
+use crate::interval::{Interval, Surrounds};
+use crate::light::SampleLight;
+use crate::material::Material;
+use crate::point3::Point3;
+use crate::ray::{At, Direction, Origin, Ray};
+use crate::rtweekend::{random_double, INF, PI};
+use crate::sphere::{Hit, HitRecord, New, SetFaceNormal};
+use crate::vec3::{Dot, Length, LengthSquared, Onb, Vec3};
+
+/// Infinite plane through `point`, facing `normal`. Its UVs are distances
+/// along two fixed directions in the plane, so textures repeat across it.
+#[derive(Clone)]
+pub struct Plane {
+    pub(crate) point: Point3,
+    pub(crate) normal: Vec3,
+    pub(crate) mat: Material,
+    uvw: Onb,
+}
+
+/// Flat disk of `radius` around `center`, facing `normal`. UVs are polar:
+/// u is the angle around the center, v the distance from it over the radius.
+#[derive(Clone)]
+pub struct Disk {
+    pub(crate) center: Point3,
+    pub(crate) normal: Vec3,
+    pub(crate) radius: f32,
+    pub(crate) mat: Material,
+    uvw: Onb,
+}
+
+/// Distance along `r` to the plane through `point` with unit `normal`, if
+/// it falls within `ray_t`.
+fn hit_plane(point: Point3, normal: Vec3, r: Ray, ray_t: Interval) -> Option<f32> {
+    let denom = normal.dot(r.direction());
+    // No hit if the ray is parallel to the plane.
+    if denom.abs() < 1e-8 {
+        return None;
+    }
+    let t = (point - r.origin()).dot(normal) / denom;
+    ray_t.surrounds(t).then_some(t)
+}
+
+impl Plane {
+    pub fn new(point: Point3, normal: Vec3, mat: Material) -> Self {
+        let uvw = Onb::new(normal);
+        Self { point, normal: uvw.w, mat, uvw }
+    }
+}
+
+impl Hit for Plane {
+    fn hit<'a>(&'a self, r: Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        let Some(t) = hit_plane(self.point, self.normal, r, ray_t) else {
+            return false;
+        };
+        rec.t = t;
+        rec.p = r.at(t);
+        let offset = rec.p - self.point;
+        rec.u = offset.dot(self.uvw.u);
+        rec.v = offset.dot(self.uvw.v);
+        rec.mat = &self.mat;
+        rec.set_face_normal(r, self.normal);
+        true
+    }
+}
+
+impl BoundingBox for Plane {
+    fn bounding_box(&self) -> Aabb {
+        Aabb::UNIVERSE
+    }
+}
+
+impl Disk {
+    pub fn new(center: Point3, normal: Vec3, radius: f32, mat: Material) -> Self {
+        let uvw = Onb::new(normal);
+        Self { center, normal: uvw.w, radius, mat, uvw }
+    }
+
+    fn area(&self) -> f32 {
+        PI * self.radius * self.radius
+    }
+}
+
+impl Hit for Disk {
+    fn hit<'a>(&'a self, r: Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        let Some(t) = hit_plane(self.center, self.normal, r, ray_t) else {
+            return false;
+        };
+        let p = r.at(t);
+        let offset = p - self.center;
+        let distance_squared = offset.length_squared();
+        if distance_squared > self.radius * self.radius {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = p;
+        let phi = offset.dot(self.uvw.v).atan2(offset.dot(self.uvw.u));
+        rec.u = (phi + PI) / (2.0 * PI);
+        rec.v = distance_squared.sqrt() / self.radius;
+        rec.mat = &self.mat;
+        rec.set_face_normal(r, self.normal);
+        true
+    }
+}
+
+impl BoundingBox for Disk {
+    fn bounding_box(&self) -> Aabb {
+        // The disk's extent along an axis shrinks as the normal tilts towards it.
+        let n = self.normal;
+        let extent = |c: f32| self.radius * (1.0 - c * c).max(0.0).sqrt();
+        let half = Vec3::new(extent(n.x), extent(n.y), extent(n.z));
+        Aabb::from_points(self.center - half, self.center + half)
+    }
+}
+
+impl SampleLight for Disk {
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f32 {
+        let mut rec = HitRecord::new();
+        if !self.hit(Ray::new(origin, direction), Interval::new(0.001, INF), &mut rec) {
+            return 0.0;
+        }
+
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = (direction.dot(rec.normal) / direction.length()).abs();
+
+        distance_squared / (cosine * self.area())
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        // Square root of the radius fraction keeps the samples uniform by area.
+        let r = self.radius * random_double().sqrt();
+        let phi = 2.0 * PI * random_double();
+        let p = self.center + self.uvw.transform(Vec3::new(r * phi.cos(), r * phi.sin(), 0.0));
+        p - origin
+    }
+}
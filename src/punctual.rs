@@ -0,0 +1,108 @@
+use crate::color::Color;
+use crate::point3::Point3;
+use crate::rtweekend::INF;
+use crate::vec3::{Dot, Length, Unit, Vec3};
+
+/// Light from a single point or direction. Nothing can hit such a light, so
+/// the integrators only ever reach it with shadow rays.
+pub trait Illuminate {
+    /// Light arriving at `p`: the unit direction towards the light, the
+    /// distance to it and the irradiance it delivers to a surface facing it.
+    /// `None` when `p` is outside the light's reach.
+    fn illuminate(&self, p: Point3) -> Option<(Vec3, f32, Color)>;
+}
+
+/// Light shining equally in all directions from `position`, falling off
+/// with the square of the distance. `intensity` is per unit solid angle.
+#[derive(Clone)]
+pub struct PointLight {
+    pub position: Point3,
+    pub intensity: Color,
+}
+
+/// A point light restricted to a cone around `direction`. It is at full
+/// strength within `cos_inner` of the axis and fades out smoothly towards
+/// `cos_outer`.
+#[derive(Clone)]
+pub struct SpotLight {
+    pub position: Point3,
+    pub direction: Vec3,
+    pub intensity: Color,
+    cos_inner: f32,
+    cos_outer: f32,
+}
+
+/// Parallel light travelling along `direction`, as from a very distant sun.
+#[derive(Clone)]
+pub struct DirectionalLight {
+    pub direction: Vec3,
+    pub irradiance: Color,
+}
+
+impl PointLight {
+    pub fn new(position: Point3, intensity: Color) -> Self {
+        Self { position, intensity }
+    }
+}
+
+impl Illuminate for PointLight {
+    fn illuminate(&self, p: Point3) -> Option<(Vec3, f32, Color)> {
+        let to_light = self.position - p;
+        let distance = to_light.length();
+        if distance <= 0.0 {
+            return None;
+        }
+        Some((to_light / distance, distance, self.intensity / (distance * distance)))
+    }
+}
+
+impl SpotLight {
+    /// `inner_angle` and `outer_angle` are measured from the axis, in
+    /// degrees. The inner angle is clamped to the outer one.
+    pub fn new(position: Point3, direction: Vec3, intensity: Color, inner_angle: f32, outer_angle: f32) -> Self {
+        let cos_outer = outer_angle.to_radians().cos();
+        let cos_inner = inner_angle.min(outer_angle).to_radians().cos();
+        Self { position, direction: direction.unit(), intensity, cos_inner, cos_outer }
+    }
+
+    /// Fraction of the full intensity sent along unit `direction`.
+    fn falloff(&self, direction: Vec3) -> f32 {
+        let cos_theta = direction.dot(self.direction);
+        if cos_theta >= self.cos_inner {
+            return 1.0;
+        }
+        if cos_theta <= self.cos_outer {
+            return 0.0;
+        }
+        let t = (cos_theta - self.cos_outer) / (self.cos_inner - self.cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Illuminate for SpotLight {
+    fn illuminate(&self, p: Point3) -> Option<(Vec3, f32, Color)> {
+        let to_light = self.position - p;
+        let distance = to_light.length();
+        if distance <= 0.0 {
+            return None;
+        }
+        let direction = to_light / distance;
+        let falloff = self.falloff(-direction);
+        if falloff <= 0.0 {
+            return None;
+        }
+        Some((direction, distance, self.intensity * (falloff / (distance * distance))))
+    }
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, irradiance: Color) -> Self {
+        Self { direction: direction.unit(), irradiance }
+    }
+}
+
+impl Illuminate for DirectionalLight {
+    fn illuminate(&self, _p: Point3) -> Option<(Vec3, f32, Color)> {
+        Some((-self.direction, INF, self.irradiance))
+    }
+}
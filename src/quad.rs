@@ -0,0 +1,141 @@
+use crate::aabb::{Aabb, BoundingBox};
+use crate::interval::{Contains, Interval, Surrounds};
+use crate::light::SampleLight;
+use crate::material::Material;
+use crate::point3::Point3;
+use crate::ray::{At, Direction, Origin, Ray};
+use crate::rtweekend::{random_double, INF};
+use crate::sphere::{Hit, HitRecord, HittableObject, New, SetFaceNormal};
+use crate::vec3::{cross, Dot, Length, LengthSquared, Unit, Vec3};
+
+/// Parallelogram spanned by the edges `u` and `v` from the corner `q`.
+pub struct Quad {
+    pub(crate) q: Point3,
+    pub(crate) u: Vec3,
+    pub(crate) v: Vec3,
+    pub(crate) mat: Material,
+    normal: Vec3,
+    d: f32,
+    w: Vec3,
+    area: f32,
+}
+
+impl Quad {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, mat: Material) -> Self {
+        let n = cross(u, v);
+        let normal = n.unit();
+        let d = normal.dot(q);
+        let w = n / n.length_squared();
+        let area = n.length();
+        Self { q, u, v, mat, normal, d, w, area }
+    }
+}
+
+/// The six faces of the axis-aligned box with opposite corners `a` and `b`.
+pub fn axis_aligned_box(a: Point3, b: Point3, mat: Material) -> Vec<HittableObject> {
+    let min = Point3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z));
+    let max = Point3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z));
+    let dx = Vec3::new(max.x - min.x, 0.0, 0.0);
+    let dy = Vec3::new(0.0, max.y - min.y, 0.0);
+    let dz = Vec3::new(0.0, 0.0, max.z - min.z);
+    oriented_box(min, [dx, dy, dz], mat)
+}
+
+/// The six faces of the box spanned by three edges leaving `corner`. The
+/// edges are normally perpendicular, though any parallelepiped works. Every
+/// face normal points out of the box.
+pub fn oriented_box(corner: Point3, edges: [Vec3; 3], mat: Material) -> Vec<HittableObject> {
+    let [mut a, mut b, c] = edges;
+    if cross(a, b).dot(c) < 0.0 {
+        std::mem::swap(&mut a, &mut b);
+    }
+    let o = corner;
+    [
+        Quad::new(o + c, a, b, mat.clone()),         // far along c
+        Quad::new(o + a, -a, b, mat.clone()),        // near along c
+        Quad::new(o + a + c, -c, b, mat.clone()),    // far along a
+        Quad::new(o, c, b, mat.clone()),             // near along a
+        Quad::new(o + b + c, a, -c, mat.clone()),    // far along b
+        Quad::new(o, a, c, mat),                     // near along b
+    ]
+    .into_iter()
+    .map(HittableObject::Quad)
+    .collect()
+}
+
+impl Hit for Quad {
+    fn hit<'a>(&'a self, r: Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        let denom = self.normal.dot(r.direction());
+
+        // No hit if the ray is parallel to the plane.
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+
+        let t = (self.d - self.normal.dot(r.origin())) / denom;
+        if !ray_t.surrounds(t) {
+            return false;
+        }
+
+        // Express the hit point in the (u, v) frame and check it lies inside.
+        let intersection = r.at(t);
+        let planar_hitpt_vector = intersection - self.q;
+        let alpha = self.w.dot(cross(planar_hitpt_vector, self.v));
+        let beta = self.w.dot(cross(self.u, planar_hitpt_vector));
+
+        let unit_interval = Interval::new(0.0, 1.0);
+        if !unit_interval.contains(alpha) || !unit_interval.contains(beta) {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = intersection;
+        rec.u = alpha;
+        rec.v = beta;
+        rec.mat = &self.mat;
+        rec.set_face_normal(r, self.normal);
+        true
+    }
+}
+
+impl BoundingBox for Quad {
+    fn bounding_box(&self) -> Aabb {
+        let bbox_diagonal1 = Aabb::from_points(self.q, self.q + self.u + self.v);
+        let bbox_diagonal2 = Aabb::from_points(self.q + self.u, self.q + self.v);
+        Aabb::enclosing(bbox_diagonal1, bbox_diagonal2)
+    }
+}
+
+impl SampleLight for Quad {
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f32 {
+        let mut rec = HitRecord::new();
+        if !self.hit(Ray::new(origin, direction), Interval::new(0.001, INF), &mut rec) {
+            return 0.0;
+        }
+
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = (direction.dot(rec.normal) / direction.length()).abs();
+
+        distance_squared / (cosine * self.area)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        let p = self.q + (self.u * random_double()) + (self.v * random_double());
+        p - origin
+    }
+}
+
+impl Clone for Quad {
+    fn clone(&self) -> Self {
+        Quad {
+            q: self.q,
+            u: self.u,
+            v: self.v,
+            mat: self.mat.clone(),
+            normal: self.normal,
+            d: self.d,
+            w: self.w,
+            area: self.area,
+        }
+    }
+}
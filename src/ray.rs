@@ -1,4 +1,4 @@
-use crate::vec3::{mul, Vec3};
+use crate::vec3::Vec3;
 use crate::point3::Point3;
 
 pub trait At {
@@ -13,10 +13,16 @@ pub trait Direction {
     fn direction(&self) -> Vec3;
 }
 
+pub trait Time {
+    fn time(&self) -> f32;
+}
+
 #[derive(Debug)]
 pub struct Ray {
     pub(crate) orig: Point3,
     pub(crate) dir: Vec3,
+    /// Moment within the camera's shutter interval at which the ray is cast.
+    pub(crate) tm: f32,
 }
 
 impl Origin for Ray {
@@ -31,6 +37,12 @@ impl Direction for Ray {
     }
 }
 
+impl Time for Ray {
+    fn time(&self) -> f32 {
+        self.tm
+    }
+}
+
 impl At for Ray {
     fn at(&self, t: f32) -> Vec3 {
         self.orig +self.dir * t
@@ -47,6 +59,10 @@ impl Clone for Ray {
 
 impl Ray {
     pub fn new(origin: Point3, direction: Vec3) -> Self {
-        Self { orig: origin, dir: direction }
+        Self { orig: origin, dir: direction, tm: 0.0 }
+    }
+
+    pub fn with_time(origin: Point3, direction: Vec3, time: f32) -> Self {
+        Self { orig: origin, dir: direction, tm: time }
     }
 }
@@ -4,7 +4,7 @@ use rand::Rng;
 
 
 pub const INF: f32 = f32::INFINITY;
-pub const PI: f32 = 3.1415926535897932385;
+pub const PI: f32 = std::f32::consts::PI;
 
 pub fn degrees_to_radians(degrees: f32) -> f32 {
     degrees * PI / 180.0
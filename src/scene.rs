@@ -0,0 +1,1138 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::bvh::Bvh;
+use crate::camera::{Background, Camera};
+use crate::color::Color;
+use crate::dispersion::{Dispersion, DispersionPreset};
+use crate::environment::EnvironmentMap;
+use crate::image::Image;
+use crate::instance::Instance;
+use crate::integrator::{
+    AmbientOcclusionIntegrator, DirectLightingIntegrator, Heuristic, IntegratorKind, MisIntegrator, PathIntegrator,
+    WhittedIntegrator, DEFAULT_RR_DEPTH,
+};
+use crate::light::Light;
+use crate::material::{
+    Conductor, ConductorPreset, Dialectric, DiffuseLight, Isotropic, Glossy, Lambertian, Material, Metal, Principled,
+};
+use crate::medium::ConstantMedium;
+use crate::obj;
+use crate::point3::Point3;
+use crate::plane::{Disk, Plane};
+use crate::quad::{axis_aligned_box, oriented_box, Quad};
+use crate::punctual::{DirectionalLight, PointLight, SpotLight};
+use crate::rtweekend::INF;
+use crate::sky::Sky;
+use crate::spectrum::{LAMBDA_MAX, LAMBDA_MIN};
+use crate::sphere::{Add, HittableList, HittableObject, MovingSphere, New, Sphere};
+use crate::texture::{CheckerTexture, ImageTexture, NoiseStyle, NoiseTexture, Texture};
+use crate::transform::Transform;
+use crate::triangle::Triangle;
+use crate::vec3::{cross, LengthSquared, Vec3};
+
+/// Everything needed to render an image: the objects, the lights among them
+/// and the camera looking at them.
+pub struct Scene {
+    pub world: HittableList,
+    pub lights: Vec<Light>,
+    pub camera: Camera,
+}
+
+/// Problem found while loading a scene file. The message includes the line
+/// and column when the problem can be pinned to one.
+#[derive(Debug)]
+pub struct SceneError {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.message)
+    }
+}
+
+impl Error for SceneError {}
+
+// The structs below mirror the TOML layout. A scene file looks like:
+//
+//     [render]
+//     image_width = 400
+//     samples_per_pixel = 100
+//
+//     [camera]
+//     lookfrom = [13, 2, 3]
+//     vfov = 20
+//
+//     [textures.checker]
+//     type = "checker"
+//     scale = 0.32
+//     even = [0.2, 0.3, 0.1]
+//     odd = [0.9, 0.9, 0.9]
+//
+//     [materials.ground]
+//     type = "lambertian"
+//     albedo = "checker"      # or a color, e.g. [0.5, 0.5, 0.5]
+//
+//     [[planes]]
+//     point = [0, 0, 0]
+//     normal = [0, 1, 0]
+//     material = "ground"
+//
+// Other shapes are listed the same way under `spheres`, `moving_spheres`,
+// `triangles`, `quads`, `boxes`, `disks` and `meshes`.
+//
+// Shapes can also be grouped under a name and placed any number of times,
+// sharing one copy of the geometry:
+//
+//     [objects.pillar]
+//     meshes = [{ file = "pillar.obj" }]
+//
+//     [[instances]]
+//     object = "pillar"
+//     scale = 2               # or per axis, e.g. [1, 2, 1]
+//     rotate = [{ axis = [0, 1, 0], angle = 45 }]
+//     translate = [3, 0, 0]
+//
+// Fog and smoke fill a closed boundary, either a sphere or a named object:
+//
+//     [[volumes]]
+//     boundary = { type = "sphere", center = [0, 1, 0], radius = 1 }
+//     density = 0.5
+//     albedo = [1, 1, 1]
+//
+// An HDR environment map can light the scene from all around, and is then
+// sampled directly like the other lights:
+//
+//     [render]
+//     environment = { file = "sky.hdr", rotation = 90, intensity = 1.5 }
+//
+// Or, for daylight, a sun and sky given by the sun's position:
+//
+//     [render]
+//     sky = { sun_elevation = 30, sun_azimuth = 45, turbidity = 3 }
+//
+// Point, spot and directional lights are cheap to render and invisible to
+// the camera:
+//
+//     [[lights]]
+//     type = "spot"
+//     position = [0, 4, 0]
+//     direction = [0, -1, 0]
+//     intensity = [20, 20, 20]
+//     inner_angle = 20
+//     outer_angle = 30
+//
+// A `metal` with `fuzz` scatters rays off its own way. A `glossy` material
+// takes the same `albedo` and `fuzz` but spreads them over a lobe, which
+// lets the integrators sample lights for it:
+//
+//     [materials.brushed]
+//     type = "glossy"
+//     albedo = [0.8, 0.8, 0.8]
+//     fuzz = 0.2
+//
+// Glass can split light into colors with an index of refraction that
+// depends on wavelength, from `bk7`, `fused_silica` or `diamond`, or from
+// Cauchy or Sellmeier coefficients:
+//
+//     [materials.prism]
+//     type = "dielectric"
+//     dispersion = "bk7"       # or e.g. { type = "cauchy", a = 1.5, b = 0.004 }
+//
+// Paths can carry sampled wavelengths instead of RGB, with colors in the
+// scene turned into smooth spectra:
+//
+//     [render]
+//     spectral = true
+//
+// Every section is optional; anything left out keeps the `Camera::new` default.
+// Spheres, triangles, quads, boxes and disks with a `diffuse_light` material
+// are also registered as lights for direct sampling, except inside named
+// objects.
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneFile {
+    #[serde(default)]
+    render: RenderDesc,
+    #[serde(default)]
+    camera: CameraDesc,
+    #[serde(default)]
+    textures: HashMap<String, Spanned<TextureDesc>>,
+    #[serde(default)]
+    materials: HashMap<String, Spanned<MaterialDesc>>,
+    #[serde(default)]
+    spheres: Vec<SphereDesc>,
+    #[serde(default)]
+    moving_spheres: Vec<MovingSphereDesc>,
+    #[serde(default)]
+    triangles: Vec<TriangleDesc>,
+    #[serde(default)]
+    quads: Vec<QuadDesc>,
+    #[serde(default)]
+    boxes: Vec<BoxDesc>,
+    #[serde(default)]
+    disks: Vec<DiskDesc>,
+    #[serde(default)]
+    planes: Vec<PlaneDesc>,
+    #[serde(default)]
+    meshes: Vec<MeshDesc>,
+    /// Named groups of shapes that only appear through `instances`.
+    #[serde(default)]
+    objects: HashMap<String, ShapesDesc>,
+    #[serde(default)]
+    instances: Vec<InstanceDesc>,
+    #[serde(default)]
+    volumes: Vec<VolumeDesc>,
+    #[serde(default)]
+    lights: Vec<LightDesc>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct ShapesDesc {
+    #[serde(default)]
+    spheres: Vec<SphereDesc>,
+    #[serde(default)]
+    moving_spheres: Vec<MovingSphereDesc>,
+    #[serde(default)]
+    triangles: Vec<TriangleDesc>,
+    #[serde(default)]
+    quads: Vec<QuadDesc>,
+    #[serde(default)]
+    boxes: Vec<BoxDesc>,
+    #[serde(default)]
+    disks: Vec<DiskDesc>,
+    #[serde(default)]
+    planes: Vec<PlaneDesc>,
+    #[serde(default)]
+    meshes: Vec<MeshDesc>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct RenderDesc {
+    image_width: Option<i32>,
+    samples_per_pixel: Option<i32>,
+    max_depth: Option<i32>,
+    threads: Option<usize>,
+    tile_size: Option<i32>,
+    /// Solid background color. The sky gradient is used when left out.
+    background: Option<[f32; 3]>,
+    /// HDR image lighting the scene from all around, used instead of `background`.
+    environment: Option<EnvironmentDesc>,
+    /// Analytic daylight, used instead of `background`.
+    sky: Option<SkyDesc>,
+    /// One of `path`, `mis`, `whitted`, `ambient_occlusion` and
+    /// `direct_lighting`, e.g. `{ type = "mis", heuristic = "balance" }`.
+    /// `path` when left out.
+    integrator: Option<IntegratorDesc>,
+    /// Trace sampled wavelengths instead of RGB; `--spectral` on the
+    /// command line turns this on too.
+    #[serde(default)]
+    spectral: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EnvironmentDesc {
+    /// Equirectangular `.hdr` or `.pfm` image, relative to the scene file.
+    file: Spanned<String>,
+    /// Turn about the y axis, in degrees.
+    #[serde(default)]
+    rotation: f32,
+    #[serde(default = "default_intensity")]
+    intensity: f32,
+}
+
+fn default_intensity() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SkyDesc {
+    /// Height of the sun above the horizon, in degrees from 0 to 90.
+    sun_elevation: f32,
+    /// Compass direction of the sun in degrees: 0 is towards -z, 90 towards +x.
+    #[serde(default)]
+    sun_azimuth: f32,
+    /// Haziness, from 2 for a very clear sky to 10.
+    #[serde(default = "default_turbidity")]
+    turbidity: f32,
+    #[serde(default = "default_intensity")]
+    intensity: f32,
+}
+
+fn default_turbidity() -> f32 {
+    3.0
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum IntegratorDesc {
+    /// `rr_depth` is the number of bounces before Russian roulette starts.
+    Path { rr_depth: Option<i32> },
+    Mis { #[serde(default)] heuristic: HeuristicDesc, rr_depth: Option<i32> },
+    Whitted,
+    /// Occlusion is looked for within `distance`, or without limit when left out.
+    AmbientOcclusion { distance: Option<f32> },
+    DirectLighting,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+enum HeuristicDesc {
+    Balance,
+    #[default]
+    Power,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct CameraDesc {
+    aspect_ratio: Option<f32>,
+    vfov: Option<i32>,
+    lookfrom: Option<[f32; 3]>,
+    lookat: Option<[f32; 3]>,
+    vup: Option<[f32; 3]>,
+    defocus_angle: Option<f32>,
+    focus_dist: Option<f32>,
+    shutter_open: Option<f32>,
+    shutter_close: Option<f32>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDesc {
+    Solid { color: [f32; 3] },
+    Checker { scale: f32, even: [f32; 3], odd: [f32; 3] },
+    /// PNG, PPM, HDR or PFM file, relative to the scene file.
+    Image { file: String },
+    Noise { scale: f32, #[serde(default)] style: NoiseStyleDesc },
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+enum NoiseStyleDesc {
+    Smooth,
+    Turbulence,
+    #[default]
+    Marble,
+}
+
+/// A material input given either as a plain color or as the name of a texture.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TextureRef {
+    Color([f32; 3]),
+    Named(String),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDesc {
+    Lambertian { albedo: TextureRef },
+    Metal { albedo: TextureRef, #[serde(default)] fuzz: f32 },
+    /// Like `metal`, but with the fuzz as a lobe that light sampling reaches.
+    Glossy { albedo: TextureRef, #[serde(default)] fuzz: f32 },
+    /// Frosted when `roughness` is above 0, and tinted by `absorption`, the
+    /// Beer–Lambert coefficient of each channel: light keeps
+    /// `exp(-absorption * distance)` of itself inside. Takes either a fixed
+    /// `ior` or a `dispersion` giving it by wavelength.
+    #[serde(alias = "dialectric")]
+    Dielectric {
+        ior: Option<f32>,
+        dispersion: Option<DispersionDesc>,
+        #[serde(default)]
+        roughness: f32,
+        #[serde(default)]
+        absorption: [f32; 3],
+    },
+    DiffuseLight { emit: TextureRef },
+    /// Microfacet metal, either a measured `metal` or a complex index of
+    /// refraction given as `eta` and `k`.
+    Conductor {
+        metal: Option<ConductorPresetDesc>,
+        eta: Option<[f32; 3]>,
+        k: Option<[f32; 3]>,
+        #[serde(default)]
+        roughness: f32,
+    },
+    /// Disney-style material; parameters left out keep the defaults of
+    /// `Principled::from_texture`.
+    Principled {
+        base_color: TextureRef,
+        metallic: Option<f32>,
+        roughness: Option<f32>,
+        specular: Option<f32>,
+        specular_tint: Option<f32>,
+        sheen: Option<f32>,
+        clearcoat: Option<f32>,
+        clearcoat_gloss: Option<f32>,
+        transmission: Option<f32>,
+        ior: Option<f32>,
+    },
+}
+
+/// A measured material by name, or the coefficients of one of the models,
+/// e.g. `{ type = "cauchy", a = 1.5, b = 0.004 }` with the wavelength in µm.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DispersionDesc {
+    Preset(DispersionPresetDesc),
+    Model(DispersionModelDesc),
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum DispersionPresetDesc {
+    Bk7,
+    FusedSilica,
+    Diamond,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum DispersionModelDesc {
+    Cauchy { a: f32, b: f32 },
+    Sellmeier { b: [f32; 3], c: [f32; 3] },
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum ConductorPresetDesc {
+    Gold,
+    Copper,
+    #[serde(alias = "aluminum")]
+    Aluminium,
+    Silver,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SphereDesc {
+    center: [f32; 3],
+    radius: f32,
+    material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MovingSphereDesc {
+    /// Center at `time0` and at `time1`.
+    center0: [f32; 3],
+    center1: [f32; 3],
+    #[serde(default)]
+    time0: f32,
+    #[serde(default = "default_time1")]
+    time1: f32,
+    radius: f32,
+    material: Spanned<String>,
+}
+
+fn default_time1() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TriangleDesc {
+    vertices: [[f32; 3]; 3],
+    material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QuadDesc {
+    /// One corner, and the two edges leaving it.
+    q: [f32; 3],
+    u: [f32; 3],
+    v: [f32; 3],
+    material: Spanned<String>,
+}
+
+/// A box given by two opposite corners, or by one corner and the three
+/// edges leaving it for boxes that aren't axis-aligned.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BoxDesc {
+    min: Option<[f32; 3]>,
+    max: Option<[f32; 3]>,
+    corner: Option<[f32; 3]>,
+    edges: Option<[[f32; 3]; 3]>,
+    material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DiskDesc {
+    center: [f32; 3],
+    normal: [f32; 3],
+    radius: f32,
+    material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PlaneDesc {
+    point: [f32; 3],
+    normal: [f32; 3],
+    material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MeshDesc {
+    /// OBJ file, relative to the scene file. Materials come from its MTL libraries.
+    file: Spanned<String>,
+}
+
+/// A named object placed by scaling it, then applying the rotations in order,
+/// then translating it.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InstanceDesc {
+    object: Spanned<String>,
+    scale: Option<Spanned<ScaleDesc>>,
+    #[serde(default)]
+    rotate: Vec<RotateDesc>,
+    translate: Option<[f32; 3]>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScaleDesc {
+    Uniform(f32),
+    PerAxis([f32; 3]),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RotateDesc {
+    axis: [f32; 3],
+    /// Counter-clockwise, in degrees.
+    angle: f32,
+}
+
+/// Constant-density medium inside a boundary, which must be closed and
+/// convex for rays to enter and leave it correctly.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VolumeDesc {
+    boundary: BoundaryDesc,
+    density: f32,
+    albedo: Spanned<TextureRef>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum BoundaryDesc {
+    Sphere { center: [f32; 3], radius: f32 },
+    /// One of the `[objects]`; its materials are ignored.
+    Object { name: Spanned<String> },
+}
+
+/// Point, spot or directional light. They light the scene without being
+/// visible themselves.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum LightDesc {
+    Point { position: [f32; 3], intensity: [f32; 3] },
+    /// Full strength up to `inner_angle` from `direction` and none past
+    /// `outer_angle`, both in degrees.
+    Spot { position: [f32; 3], direction: [f32; 3], intensity: [f32; 3], inner_angle: f32, outer_angle: f32 },
+    /// `direction` is the way the light travels.
+    Directional { direction: [f32; 3], irradiance: [f32; 3] },
+}
+
+fn vec3(v: [f32; 3]) -> Vec3 {
+    Vec3::new(v[0], v[1], v[2])
+}
+
+struct Loader<'a> {
+    path: &'a Path,
+    source: &'a str,
+    textures: HashMap<String, Texture>,
+    materials: HashMap<String, Material>,
+}
+
+impl Loader<'_> {
+    fn error_at(&self, span: std::ops::Range<usize>, message: String) -> SceneError {
+        let before = &self.source[..span.start.min(self.source.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+        SceneError {
+            path: self.path.to_path_buf(),
+            message: format!("line {}, column {}: {}", line, column, message),
+        }
+    }
+
+    fn error(&self, message: String) -> SceneError {
+        SceneError { path: self.path.to_path_buf(), message }
+    }
+
+    /// Resolves a texture input of `owner`, which names it in error messages
+    /// pointing at `span`.
+    fn texture(&self, owner: &str, span: std::ops::Range<usize>, texture: &TextureRef) -> Result<Texture, SceneError> {
+        match texture {
+            TextureRef::Color(color) => Ok(Texture::solid(vec3(*color))),
+            TextureRef::Named(name) => self.textures.get(name).cloned().ok_or_else(|| {
+                self.error_at(span, format!("{}: unknown texture '{}'", owner, name))
+            }),
+        }
+    }
+
+    fn material(&self, name: &Spanned<String>) -> Result<Material, SceneError> {
+        self.materials.get(name.get_ref()).cloned().ok_or_else(|| {
+            let mut known: Vec<&str> = self.materials.keys().map(String::as_str).collect();
+            known.sort();
+            self.error_at(
+                name.span(),
+                format!("unknown material '{}' (defined: {})", name.get_ref(), known.join(", ")),
+            )
+        })
+    }
+}
+
+impl Loader<'_> {
+    /// Builds every shape in `desc`, paired with whether it should also be
+    /// sampled as a light.
+    fn shapes(&self, desc: &ShapesDesc, base_dir: &Path) -> Result<Vec<(HittableObject, bool)>, SceneError> {
+        let is_light = |mat: &Material| matches!(mat, Material::DiffuseLight(_));
+        let mut shapes = Vec::new();
+        for sphere in &desc.spheres {
+            if sphere.radius <= 0.0 {
+                return Err(self.error_at(sphere.material.span(), "sphere `radius` must be positive".to_string()));
+            }
+            let mat = self.material(&sphere.material)?;
+            let light = is_light(&mat);
+            shapes.push((HittableObject::Sphere(Sphere { center: vec3(sphere.center), radius: sphere.radius, mat }), light));
+        }
+        for sphere in &desc.moving_spheres {
+            if sphere.radius <= 0.0 {
+                return Err(self.error_at(sphere.material.span(), "sphere `radius` must be positive".to_string()));
+            }
+            let mat = self.material(&sphere.material)?;
+            // Light sampling has no notion of ray time, so a moving emitter is
+            // never registered as a light and only shows up where seen directly.
+            let sphere = MovingSphere::new(
+                vec3(sphere.center0),
+                vec3(sphere.center1),
+                sphere.time0,
+                sphere.time1,
+                sphere.radius,
+                mat,
+            );
+            shapes.push((HittableObject::MovingSphere(sphere), false));
+        }
+        for triangle in &desc.triangles {
+            let [v0, v1, v2] = triangle.vertices.map(vec3);
+            let mat = self.material(&triangle.material)?;
+            let light = is_light(&mat);
+            shapes.push((HittableObject::Triangle(Triangle::new(v0, v1, v2, mat)), light));
+        }
+        for quad in &desc.quads {
+            if cross(vec3(quad.u), vec3(quad.v)).length_squared() == 0.0 {
+                return Err(self.error_at(quad.material.span(), "quad edges `u` and `v` must not be parallel".to_string()));
+            }
+            let mat = self.material(&quad.material)?;
+            let light = is_light(&mat);
+            shapes.push((HittableObject::Quad(Quad::new(vec3(quad.q), vec3(quad.u), vec3(quad.v), mat)), light));
+        }
+        for desc in &desc.boxes {
+            let mat = self.material(&desc.material)?;
+            let faces = match (desc.min, desc.max, desc.corner, desc.edges) {
+                (Some(min), Some(max), None, None) => axis_aligned_box(vec3(min), vec3(max), mat),
+                (None, None, Some(corner), Some(edges)) => oriented_box(vec3(corner), edges.map(vec3), mat),
+                _ => {
+                    return Err(self.error_at(
+                        desc.material.span(),
+                        "a box needs either `min` and `max`, or `corner` and `edges`".to_string(),
+                    ))
+                }
+            };
+            for face in faces {
+                let light = matches!(&face, HittableObject::Quad(quad) if is_light(&quad.mat));
+                shapes.push((face, light));
+            }
+        }
+        for disk in &desc.disks {
+            if disk.radius <= 0.0 {
+                return Err(self.error_at(disk.material.span(), "disk `radius` must be positive".to_string()));
+            }
+            let mat = self.material(&disk.material)?;
+            let light = is_light(&mat);
+            let disk = Disk::new(vec3(disk.center), vec3(disk.normal), disk.radius, mat);
+            shapes.push((HittableObject::Disk(disk), light));
+        }
+        for plane in &desc.planes {
+            // An infinite plane can't be sampled as a light.
+            let mat = self.material(&plane.material)?;
+            shapes.push((HittableObject::Plane(Plane::new(vec3(plane.point), vec3(plane.normal), mat)), false));
+        }
+        for mesh in &desc.meshes {
+            let model = obj::load_obj(base_dir.join(mesh.file.get_ref()))
+                .map_err(|e| self.error_at(mesh.file.span(), e.to_string()))?;
+            shapes.extend(model.into_objects().into_iter().map(|object| (object, false)));
+        }
+        Ok(shapes)
+    }
+
+    fn transform(&self, desc: &InstanceDesc) -> Result<Transform, SceneError> {
+        let mut transform = Transform::IDENTITY;
+        if let Some(scale) = &desc.scale {
+            let factors = match scale.get_ref() {
+                ScaleDesc::Uniform(s) => Vec3::new(*s, *s, *s),
+                ScaleDesc::PerAxis(v) => vec3(*v),
+            };
+            if factors.x == 0.0 || factors.y == 0.0 || factors.z == 0.0 {
+                return Err(self.error_at(scale.span(), "scale factors must be non-zero".to_string()));
+            }
+            transform = transform.then(&Transform::scale(factors));
+        }
+        for rotation in &desc.rotate {
+            transform = transform.then(&Transform::rotate(vec3(rotation.axis), rotation.angle));
+        }
+        if let Some(offset) = desc.translate {
+            transform = transform.then(&Transform::translate(vec3(offset)));
+        }
+        Ok(transform)
+    }
+}
+
+/// Parses a TOML scene description and builds the world and camera it describes.
+pub fn load_scene(path: impl AsRef<Path>) -> Result<Scene, SceneError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path)
+        .map_err(|e| SceneError { path: path.to_path_buf(), message: e.to_string() })?;
+    let file: SceneFile = toml::from_str(&source)
+        .map_err(|e| SceneError { path: path.to_path_buf(), message: e.to_string() })?;
+
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+    let mut loader = Loader { path, source: &source, textures: HashMap::new(), materials: HashMap::new() };
+    for (name, desc) in file.textures {
+        let span = desc.span();
+        let texture = match desc.into_inner() {
+            TextureDesc::Solid { color } => Texture::solid(vec3(color)),
+            TextureDesc::Checker { scale, even, odd } => {
+                if scale == 0.0 {
+                    return Err(loader.error_at(span, format!("texture '{}': `scale` must be non-zero", name)));
+                }
+                Texture::Checker(CheckerTexture::new(scale, Texture::solid(vec3(even)), Texture::solid(vec3(odd))))
+            }
+            TextureDesc::Image { file } => {
+                let image = Image::load(base_dir.join(&file))
+                    .map_err(|e| loader.error_at(span, format!("texture '{}': {}", name, e)))?;
+                Texture::Image(ImageTexture::new(image))
+            }
+            TextureDesc::Noise { scale, style } => {
+                let style = match style {
+                    NoiseStyleDesc::Smooth => NoiseStyle::Smooth,
+                    NoiseStyleDesc::Turbulence => NoiseStyle::Turbulence,
+                    NoiseStyleDesc::Marble => NoiseStyle::Marble,
+                };
+                Texture::Noise(NoiseTexture::new(scale, style))
+            }
+        };
+        loader.textures.insert(name, texture);
+    }
+    for (name, desc) in &file.materials {
+        let owner = format!("material '{}'", name);
+        let span = desc.span();
+        let material = match desc.get_ref() {
+            MaterialDesc::Lambertian { albedo } => {
+                Material::Lambertian(Lambertian::from_texture(loader.texture(&owner, span.clone(), albedo)?))
+            }
+            MaterialDesc::Metal { albedo, fuzz } => {
+                Material::Metal(Metal::from_texture(loader.texture(&owner, span.clone(), albedo)?, *fuzz))
+            }
+            MaterialDesc::Glossy { albedo, fuzz } => {
+                Material::Glossy(Glossy::from_texture(loader.texture(&owner, span.clone(), albedo)?, *fuzz))
+            }
+            MaterialDesc::Dielectric { ior, dispersion, roughness, absorption } => {
+                if !(0.0..=1.0).contains(roughness) {
+                    return Err(loader.error_at(span.clone(), format!("{}: `roughness` must be between 0 and 1", owner)));
+                }
+                if absorption.iter().any(|&a| a < 0.0) {
+                    return Err(loader.error_at(span.clone(), format!("{}: `absorption` can't be negative", owner)));
+                }
+                let dielectric = match (ior, dispersion) {
+                    (Some(ior), None) => {
+                        if *ior <= 0.0 {
+                            return Err(loader.error_at(span.clone(), format!("{}: `ior` must be positive", owner)));
+                        }
+                        Dialectric::rough(*ior, *roughness)
+                    }
+                    (None, Some(dispersion)) => {
+                        let dispersion = match dispersion {
+                            DispersionDesc::Preset(preset) => Dispersion::preset(match preset {
+                                DispersionPresetDesc::Bk7 => DispersionPreset::Bk7,
+                                DispersionPresetDesc::FusedSilica => DispersionPreset::FusedSilica,
+                                DispersionPresetDesc::Diamond => DispersionPreset::Diamond,
+                            }),
+                            DispersionDesc::Model(DispersionModelDesc::Cauchy { a, b }) => Dispersion::Cauchy { a: *a, b: *b },
+                            DispersionDesc::Model(DispersionModelDesc::Sellmeier { b, c }) => Dispersion::Sellmeier { b: *b, c: *c },
+                        };
+                        // Checked every nanometre, which also catches a
+                        // Sellmeier pole inside the range.
+                        let steps = (LAMBDA_MAX - LAMBDA_MIN) as i32;
+                        if (0..=steps).map(|i| dispersion.ior(LAMBDA_MIN + i as f32)).any(|n| !(n > 0.0 && n.is_finite())) {
+                            return Err(loader.error_at(
+                                span.clone(),
+                                format!("{}: `dispersion` must give a positive `ior` from {} to {} nm", owner, LAMBDA_MIN, LAMBDA_MAX),
+                            ));
+                        }
+                        Dialectric::dispersive(dispersion, *roughness)
+                    }
+                    _ => {
+                        return Err(loader.error_at(span.clone(), format!("{}: a dielectric needs either `ior` or `dispersion`", owner)))
+                    }
+                };
+                Material::Dialectric(Dialectric { absorption: vec3(*absorption), ..dielectric })
+            }
+            MaterialDesc::DiffuseLight { emit } => {
+                Material::DiffuseLight(DiffuseLight::from_texture(loader.texture(&owner, span.clone(), emit)?))
+            }
+            MaterialDesc::Conductor { metal, eta, k, roughness } => {
+                if !(0.0..=1.0).contains(roughness) {
+                    return Err(loader.error_at(span.clone(), format!("{}: `roughness` must be between 0 and 1", owner)));
+                }
+                let conductor = match (metal, eta, k) {
+                    (Some(metal), None, None) => {
+                        let preset = match metal {
+                            ConductorPresetDesc::Gold => ConductorPreset::Gold,
+                            ConductorPresetDesc::Copper => ConductorPreset::Copper,
+                            ConductorPresetDesc::Aluminium => ConductorPreset::Aluminium,
+                            ConductorPresetDesc::Silver => ConductorPreset::Silver,
+                        };
+                        Conductor::preset(preset, *roughness)
+                    }
+                    (None, Some(eta), Some(k)) => Conductor::new(vec3(*eta), vec3(*k), *roughness),
+                    _ => {
+                        return Err(loader.error_at(span.clone(), format!("{}: a conductor needs either `metal`, or `eta` and `k`", owner)))
+                    }
+                };
+                Material::Conductor(conductor)
+            }
+            MaterialDesc::Principled {
+                base_color,
+                metallic,
+                roughness,
+                specular,
+                specular_tint,
+                sheen,
+                clearcoat,
+                clearcoat_gloss,
+                transmission,
+                ior,
+            } => {
+                let mut principled = Principled::from_texture(loader.texture(&owner, span.clone(), base_color)?);
+                let parameters = [
+                    ("metallic", metallic, &mut principled.metallic),
+                    ("roughness", roughness, &mut principled.roughness),
+                    ("specular", specular, &mut principled.specular),
+                    ("specular_tint", specular_tint, &mut principled.specular_tint),
+                    ("sheen", sheen, &mut principled.sheen),
+                    ("clearcoat", clearcoat, &mut principled.clearcoat),
+                    ("clearcoat_gloss", clearcoat_gloss, &mut principled.clearcoat_gloss),
+                    ("transmission", transmission, &mut principled.transmission),
+                ];
+                for (key, value, field) in parameters {
+                    if let Some(v) = *value {
+                        if !(0.0..=1.0).contains(&v) {
+                            return Err(loader.error_at(span.clone(), format!("{}: `{}` must be between 0 and 1", owner, key)));
+                        }
+                        *field = v;
+                    }
+                }
+                if let Some(v) = *ior {
+                    if v <= 0.0 {
+                        return Err(loader.error_at(span.clone(), format!("{}: `ior` must be positive", owner)));
+                    }
+                    principled.ior = v;
+                }
+                Material::Principled(principled)
+            }
+        };
+        loader.materials.insert(name.clone(), material);
+    }
+
+    let mut world = HittableList::new();
+    let mut lights = Vec::new();
+    let top_level = ShapesDesc {
+        spheres: file.spheres,
+        moving_spheres: file.moving_spheres,
+        triangles: file.triangles,
+        quads: file.quads,
+        boxes: file.boxes,
+        disks: file.disks,
+        planes: file.planes,
+        meshes: file.meshes,
+    };
+    for (object, is_light) in loader.shapes(&top_level, base_dir)? {
+        if is_light {
+            lights.push(Light::Area(object.clone()));
+        }
+        world.add(object);
+    }
+
+    let mut objects = HashMap::new();
+    for (name, desc) in &file.objects {
+        let mut list = HittableList::new();
+        for (object, _) in loader.shapes(desc, base_dir)? {
+            list.add(object);
+        }
+        let object = match list.objects.len() {
+            0 => return Err(loader.error(format!("object '{}' has no shapes", name))),
+            1 => list.objects.pop().unwrap(),
+            _ => HittableObject::Bvh(Bvh::new(list)),
+        };
+        objects.insert(name.as_str(), Arc::new(object));
+    }
+    for instance in &file.instances {
+        let object = objects.get(instance.object.get_ref().as_str()).ok_or_else(|| {
+            loader.error_at(instance.object.span(), format!("unknown object '{}'", instance.object.get_ref()))
+        })?;
+        let transform = loader.transform(instance)?;
+        world.add(HittableObject::Instance(Instance::new(object.clone(), transform)));
+    }
+    for volume in &file.volumes {
+        let boundary = match &volume.boundary {
+            BoundaryDesc::Sphere { radius, .. } if *radius <= 0.0 => {
+                return Err(loader.error("volume boundary `radius` must be positive".to_string()));
+            }
+            BoundaryDesc::Sphere { center, radius } => Arc::new(HittableObject::Sphere(Sphere {
+                center: vec3(*center),
+                radius: *radius,
+                mat: Material::Isotropic(Isotropic::new(Color::new(1.0, 1.0, 1.0))),
+            })),
+            BoundaryDesc::Object { name } => objects.get(name.get_ref().as_str()).cloned().ok_or_else(|| {
+                loader.error_at(name.span(), format!("unknown object '{}'", name.get_ref()))
+            })?,
+        };
+        if volume.density <= 0.0 {
+            return Err(loader.error("volume density must be positive".to_string()));
+        }
+        let albedo = loader.texture("volume", volume.albedo.span(), volume.albedo.get_ref())?;
+        world.add(HittableObject::ConstantMedium(ConstantMedium::new(boundary, volume.density, albedo)));
+    }
+
+    for desc in &file.lights {
+        let light = match *desc {
+            LightDesc::Point { position, intensity } => Light::Point(PointLight::new(vec3(position), vec3(intensity))),
+            LightDesc::Spot { position, direction, intensity, inner_angle, outer_angle } => {
+                if !(0.0..180.0).contains(&outer_angle) || inner_angle < 0.0 {
+                    return Err(loader.error("spot light angles must be between 0 and 180 degrees".to_string()));
+                }
+                if vec3(direction).length_squared() == 0.0 {
+                    return Err(loader.error("spot light `direction` must not be zero".to_string()));
+                }
+                Light::Spot(SpotLight::new(vec3(position), vec3(direction), vec3(intensity), inner_angle, outer_angle))
+            }
+            LightDesc::Directional { direction, irradiance } => {
+                if vec3(direction).length_squared() == 0.0 {
+                    return Err(loader.error("directional light `direction` must not be zero".to_string()));
+                }
+                Light::Directional(DirectionalLight::new(vec3(direction), vec3(irradiance)))
+            }
+        };
+        lights.push(light);
+    }
+
+    let render = &file.render;
+    if render.image_width.is_some_and(|v| v <= 0) {
+        return Err(loader.error("`image_width` must be positive".to_string()));
+    }
+    if render.samples_per_pixel.is_some_and(|v| v <= 0) {
+        return Err(loader.error("`samples_per_pixel` must be positive".to_string()));
+    }
+    if file.camera.aspect_ratio.is_some_and(|v| v <= 0.0) {
+        return Err(loader.error("`aspect_ratio` must be positive".to_string()));
+    }
+    let mut camera = build_camera(render, &file.camera);
+    if [render.background.is_some(), render.environment.is_some(), render.sky.is_some()].iter().filter(|&&set| set).count() > 1 {
+        return Err(loader.error("only one of `background`, `environment` and `sky` can be set".to_string()));
+    }
+    if let Some(desc) = &render.environment {
+        let image = Image::load(base_dir.join(desc.file.get_ref()))
+            .map_err(|e| loader.error_at(desc.file.span(), format!("environment: {}", e)))?;
+        let map = Arc::new(EnvironmentMap::new(image, desc.rotation, desc.intensity));
+        camera.background = Background::Environment(map.clone());
+        lights.push(Light::Environment(map));
+    }
+    if let Some(desc) = &render.sky {
+        if !(0.0..=90.0).contains(&desc.sun_elevation) {
+            return Err(loader.error("sky: `sun_elevation` must be between 0 and 90 degrees".to_string()));
+        }
+        if !(2.0..=10.0).contains(&desc.turbidity) {
+            return Err(loader.error("sky: `turbidity` must be between 2 and 10".to_string()));
+        }
+        let (elevation, azimuth) = (desc.sun_elevation.to_radians(), desc.sun_azimuth.to_radians());
+        let sun_direction = Vec3::new(azimuth.sin() * elevation.cos(), elevation.sin(), -azimuth.cos() * elevation.cos());
+        let sky = Arc::new(Sky::new(sun_direction, desc.turbidity, desc.intensity));
+        camera.background = Background::Sky(sky.clone());
+        lights.push(Light::Sky(sky));
+    }
+    Ok(Scene { world, lights, camera })
+}
+
+fn build_camera(render: &RenderDesc, desc: &CameraDesc) -> Camera {
+    let mut cam = Camera::new();
+
+    if let Some(v) = render.image_width { cam.image_width = v; }
+    if let Some(v) = render.samples_per_pixel { cam.samples_per_pixel = v; }
+    if let Some(v) = render.max_depth { cam.max_depth = v; }
+    if let Some(v) = render.threads { cam.threads = v; }
+    if let Some(v) = render.tile_size { cam.tile_size = v; }
+    cam.spectral = render.spectral;
+    if let Some(v) = render.background { cam.background = Background::Solid(vec3(v)); }
+    if let Some(v) = &render.integrator {
+        cam.integrator = match v {
+            IntegratorDesc::Path { rr_depth } => {
+                IntegratorKind::Path(PathIntegrator { rr_depth: rr_depth.unwrap_or(DEFAULT_RR_DEPTH) })
+            }
+            IntegratorDesc::Mis { heuristic, rr_depth } => {
+                let heuristic = match heuristic {
+                    HeuristicDesc::Balance => Heuristic::Balance,
+                    HeuristicDesc::Power => Heuristic::Power,
+                };
+                IntegratorKind::Mis(MisIntegrator { heuristic, rr_depth: rr_depth.unwrap_or(DEFAULT_RR_DEPTH) })
+            }
+            IntegratorDesc::Whitted => IntegratorKind::Whitted(WhittedIntegrator),
+            IntegratorDesc::AmbientOcclusion { distance } => {
+                IntegratorKind::AmbientOcclusion(AmbientOcclusionIntegrator { distance: distance.unwrap_or(INF) })
+            }
+            IntegratorDesc::DirectLighting => IntegratorKind::DirectLighting(DirectLightingIntegrator),
+        };
+    }
+
+    if let Some(v) = desc.aspect_ratio { cam.aspect_ratio = v; }
+    if let Some(v) = desc.vfov { cam.vfov = v; }
+    if let Some(v) = desc.lookfrom { cam.lookfrom = vec3(v) as Point3; }
+    if let Some(v) = desc.lookat { cam.lookat = vec3(v) as Point3; }
+    if let Some(v) = desc.vup { cam.vup = vec3(v); }
+    if let Some(v) = desc.defocus_angle { cam.defocus_angle = v; }
+    if let Some(v) = desc.focus_dist { cam.focus_dist = v; }
+    if let Some(v) = desc.shutter_open { cam.shutter_open = v; }
+    if let Some(v) = desc.shutter_close { cam.shutter_close = v; }
+
+    cam
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `source` to a scene file of its own and loads it.
+    fn load(name: &str, source: &str) -> Result<Scene, SceneError> {
+        let dir = std::env::temp_dir().join(format!("pobert-scene-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("scene.toml");
+        fs::write(&path, source).unwrap();
+        load_scene(path)
+    }
+
+    fn load_error(name: &str, source: &str) -> String {
+        match load(name, source) {
+            Ok(_) => panic!("scene '{}' loaded without error", name),
+            Err(e) => e.message,
+        }
+    }
+
+    #[test]
+    fn loads_shapes_lights_and_camera() {
+        let scene = load(
+            "basic",
+            r#"
+            [render]
+            image_width = 40
+            samples_per_pixel = 2
+            [camera]
+            aspect_ratio = 2.0
+            lookfrom = [0, 1, 5]
+            [textures.checks]
+            type = "checker"
+            scale = 0.5
+            even = [0, 0, 0]
+            odd = [1, 1, 1]
+            [materials.floor]
+            type = "lambertian"
+            albedo = "checks"
+            [materials.lamp]
+            type = "diffuse_light"
+            emit = [4, 4, 4]
+            [[spheres]]
+            center = [0, 1, 0]
+            radius = 1
+            material = "floor"
+            [[quads]]
+            q = [-1, 3, -1]
+            u = [2, 0, 0]
+            v = [0, 0, 2]
+            material = "lamp"
+            [[planes]]
+            point = [0, 0, 0]
+            normal = [0, 1, 0]
+            material = "floor"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(scene.world.objects.len(), 3);
+        assert_eq!(scene.lights.len(), 1);
+        assert!(matches!(scene.lights[0], Light::Area(HittableObject::Quad(_))));
+        assert_eq!(scene.camera.image_width, 40);
+        assert_eq!(scene.camera.aspect_ratio, 2.0);
+        assert_eq!(scene.camera.lookfrom.z, 5.0);
+    }
+
+    #[test]
+    fn points_at_unknown_names() {
+        let message = load_error(
+            "material",
+            "[materials.red]\ntype = \"lambertian\"\nalbedo = [1, 0, 0]\n\n[[spheres]]\ncenter = [0, 0, 0]\nradius = 1\nmaterial = \"blue\"\n",
+        );
+        assert!(message.starts_with("line 8, column 12: unknown material 'blue'"), "{}", message);
+
+        let message = load_error("texture", "[materials.red]\ntype = \"lambertian\"\nalbedo = \"missing\"\n");
+        assert!(message.starts_with("line 1, column "), "{}", message);
+        assert!(message.contains("material 'red': unknown texture 'missing'"), "{}", message);
+
+        let message = load_error("image", "\n[textures.photo]\ntype = \"image\"\nfile = \"missing.png\"\n");
+        assert!(message.starts_with("line 2, column "), "{}", message);
+        assert!(message.contains("texture 'photo'"), "{}", message);
+    }
+
+    #[test]
+    fn rejects_degenerate_values() {
+        let cases = [
+            ("width", "[render]\nimage_width = 0\n", "`image_width` must be positive"),
+            ("aspect", "[camera]\naspect_ratio = -1.5\n", "`aspect_ratio` must be positive"),
+            ("checker", "[textures.c]\ntype = \"checker\"\nscale = 0\neven = [0, 0, 0]\nodd = [1, 1, 1]\n", "`scale` must be non-zero"),
+            (
+                "radius",
+                "[materials.m]\ntype = \"lambertian\"\nalbedo = [1, 1, 1]\n[[spheres]]\ncenter = [0, 0, 0]\nradius = 0\nmaterial = \"m\"\n",
+                "sphere `radius` must be positive",
+            ),
+            (
+                "quad",
+                "[materials.m]\ntype = \"lambertian\"\nalbedo = [1, 1, 1]\n[[quads]]\nq = [0, 0, 0]\nu = [1, 0, 0]\nv = [-2, 0, 0]\nmaterial = \"m\"\n",
+                "quad edges `u` and `v` must not be parallel",
+            ),
+            ("ior", "[materials.glass]\ntype = \"dielectric\"\nior = 0\n", "material 'glass': `ior` must be positive"),
+        ];
+        for (name, source, expected) in cases {
+            let message = load_error(name, source);
+            assert!(message.contains(expected), "{}: {}", name, message);
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use crate::color::{xyz_to_linear_srgb, Color};
+use crate::environment::{equirect_direction, EnvironmentMap};
+use crate::image::Image;
+use crate::light::SampleLight;
+use crate::point3::Point3;
+use crate::rtweekend::{random_double, PI};
+use crate::vec3::{Dot, Onb, Unit, Vec3};
+
+/// Angular radius of the sun seen from the ground, in radians.
+const SUN_ANGULAR_RADIUS: f32 = 0.004_65;
+
+/// Luminance of the sun above the atmosphere, in kcd/m².
+const SUN_LUMINANCE: f32 = 1.96e6;
+
+/// Radiance per kcd/m² of luminance. With it a white surface comes out at
+/// about 2 under the sun straight overhead, and about 1.3 with the sun 40°
+/// up.
+const RADIANCE_SCALE: f32 = 0.05;
+
+/// Size of the table the sky is importance sampled from.
+const TABLE_WIDTH: usize = 128;
+const TABLE_HEIGHT: usize = 64;
+
+/// Chance of a light sample going to the sun rather than the rest of the sky.
+const SUN_SAMPLE_PROBABILITY: f32 = 0.5;
+
+/// Daylight from the Preetham et al. analytic sky model, plus the sun as a
+/// small disk of direct light. Below the horizon the sky keeps its horizon
+/// color, so scenes without a ground don't end in black.
+pub struct Sky {
+    sun: Onb,
+    sun_cos_max: f32,
+    sun_radiance: Color,
+    sky: Perez,
+    /// The sky without the sun, tabulated for importance sampling.
+    table: EnvironmentMap,
+}
+
+/// The sky alone, without the sun disk.
+struct Perez {
+    sun_direction: Vec3,
+    /// Perez distribution coefficients A to E, for luminance Y and for the
+    /// chromaticities x and y.
+    coefficients: [[f32; 5]; 3],
+    /// Zenith Y, x and y divided by the Perez function at the zenith, so that
+    /// multiplying by the function anywhere gives the value there.
+    zenith: [f32; 3],
+    intensity: f32,
+}
+
+impl Sky {
+    /// `sun_direction` must be above the horizon. `turbidity` is the haziness
+    /// of the atmosphere, from 2 for a very clear sky to about 10 for haze;
+    /// `intensity` scales both the sky and the sun.
+    pub fn new(sun_direction: Vec3, turbidity: f32, intensity: f32) -> Self {
+        let t = turbidity;
+        let sun = Onb::new(sun_direction);
+        let theta_s = sun.w.y.clamp(0.0, 1.0).acos();
+
+        let coefficients = [
+            [0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251, 0.1206 * t - 2.5771, -0.0670 * t + 0.3703],
+            [-0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125, -0.0641 * t - 0.8989, -0.0033 * t + 0.0452],
+            [-0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102, -0.0441 * t - 1.6537, -0.0109 * t + 0.0529],
+        ];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let chromaticity = |c: [[f32; 4]; 3]| {
+            let cubic = |k: [f32; 4]| ((k[0] * theta_s + k[1]) * theta_s + k[2]) * theta_s + k[3];
+            t * t * cubic(c[0]) + t * cubic(c[1]) + cubic(c[2])
+        };
+        let zenith_x = chromaticity([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let zenith_y = chromaticity([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+        let zenith = [zenith_luminance, zenith_x, zenith_y];
+        let zenith = std::array::from_fn(|i| zenith[i] / perez_function(coefficients[i], 1.0, theta_s));
+        let sky = Perez { sun_direction: sun.w, coefficients, zenith, intensity };
+
+        // Sunlight loses blue to Rayleigh scattering and all colors to haze on
+        // its way through the atmosphere, the more so the lower the sun is.
+        // Transmittance is taken at one wavelength per channel, in µm.
+        let air_mass = 1.0 / (theta_s.cos() + 0.15 * (93.885 - theta_s.to_degrees()).powf(-1.253));
+        let beta = 0.04608 * t - 0.04586;
+        let transmittance = |lambda: f32| (-air_mass * (0.008735 * lambda.powf(-4.08) + beta * lambda.powf(-1.3))).exp();
+        let sun_radiance =
+            Color::new(transmittance(0.680), transmittance(0.550), transmittance(0.440)) * (SUN_LUMINANCE * RADIANCE_SCALE * intensity);
+
+        let pixels = (0..TABLE_WIDTH * TABLE_HEIGHT)
+            .map(|i| {
+                let u = ((i % TABLE_WIDTH) as f32 + 0.5) / TABLE_WIDTH as f32;
+                let v = 1.0 - ((i / TABLE_WIDTH) as f32 + 0.5) / TABLE_HEIGHT as f32;
+                sky.radiance(equirect_direction(u, v))
+            })
+            .collect();
+        let table = EnvironmentMap::new(Image::new(TABLE_WIDTH, TABLE_HEIGHT, pixels), 0.0, 1.0);
+
+        Self { sun, sun_cos_max: SUN_ANGULAR_RADIUS.cos(), sun_radiance, sky, table }
+    }
+
+    fn in_sun(&self, direction: Vec3) -> bool {
+        direction.dot(self.sun.w) >= self.sun_cos_max
+    }
+
+    /// Radiance arriving from `direction`.
+    pub fn value(&self, direction: Vec3) -> Color {
+        let direction = direction.unit();
+        let sky = self.sky.radiance(direction);
+        if self.in_sun(direction) {
+            sky + self.sun_radiance
+        } else {
+            sky
+        }
+    }
+
+    /// Solid-angle density of `sample` producing `direction`.
+    pub fn pdf(&self, direction: Vec3) -> f32 {
+        let direction = direction.unit();
+        let sun_pdf = if self.in_sun(direction) { 1.0 / (2.0 * PI * (1.0 - self.sun_cos_max)) } else { 0.0 };
+        SUN_SAMPLE_PROBABILITY * sun_pdf + (1.0 - SUN_SAMPLE_PROBABILITY) * self.table.pdf(direction)
+    }
+
+    /// A random direction, either uniformly within the sun disk or
+    /// following the brightness of the sky.
+    pub fn sample(&self) -> Vec3 {
+        if random_double() >= SUN_SAMPLE_PROBABILITY {
+            return self.table.sample();
+        }
+        let cos_theta = 1.0 - random_double() * (1.0 - self.sun_cos_max);
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * random_double();
+        self.sun.transform(Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, cos_theta))
+    }
+}
+
+impl Perez {
+    /// Radiance along unit `direction`.
+    fn radiance(&self, direction: Vec3) -> Color {
+        let cos_theta = direction.y.max(0.001);
+        let gamma = direction.dot(self.sun_direction).clamp(-1.0, 1.0).acos();
+        let [luminance, x, y] =
+            std::array::from_fn(|i| self.zenith[i] * perez_function(self.coefficients[i], cos_theta, gamma));
+
+        let xyz = Vec3::new(x / y * luminance, luminance, (1.0 - x - y) / y * luminance);
+        let rgb = xyz_to_linear_srgb(xyz) * (RADIANCE_SCALE * self.intensity);
+        Color::new(rgb.x.max(0.0), rgb.y.max(0.0), rgb.z.max(0.0))
+    }
+}
+
+/// The Perez sky distribution for a direction at `cos_theta` from the zenith
+/// and angle `gamma` from the sun.
+fn perez_function([a, b, c, d, e]: [f32; 5], cos_theta: f32, gamma: f32) -> f32 {
+    let cos_gamma = gamma.cos();
+    (1.0 + a * (b / cos_theta).exp()) * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+}
+
+impl SampleLight for Sky {
+    /// The sky is infinitely far away, so the density doesn't depend on `origin`.
+    fn pdf_value(&self, _origin: Point3, direction: Vec3) -> f32 {
+        self.pdf(direction)
+    }
+
+    fn random(&self, _origin: Point3) -> Vec3 {
+        self.sample()
+    }
+}
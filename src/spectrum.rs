@@ -0,0 +1,343 @@
+use std::sync::OnceLock;
+
+use crate::color::{xyz_to_linear_srgb, Color};
+use crate::vec3::Vec3;
+
+// Spectral rendering. Each path carries three wavelengths, and the colors
+// along it hold the values at those wavelengths instead of RGB. Colors from
+// the scene are turned into smooth spectra as the path picks them up, and
+// the radiance it brings back goes through the CIE color matching functions
+// to XYZ and on to linear sRGB.
+
+/// Range of wavelengths traced, in nm.
+pub const LAMBDA_MIN: f32 = 360.0;
+pub const LAMBDA_MAX: f32 = 830.0;
+
+/// Steps per sixth of the hue circle in the table of fitted spectra.
+const HUE_STEPS: usize = 64;
+
+/// Wavelengths the spectra are integrated at while fitting, 1 nm apart.
+const FIT_SAMPLES: usize = 470;
+
+/// CIE standard illuminant D65 from 360 to 830 nm in 10 nm steps, relative
+/// to 100 at 560 nm.
+const D65: [f32; 48] = [
+    46.6383, 52.0891, 49.9755, 54.6482, 82.7549, 91.486, 93.4318, 86.6823, 104.865, 117.008, 117.812, 114.861,
+    115.923, 108.811, 109.354, 107.802, 104.79, 107.689, 104.405, 104.046, 100.0, 96.3342, 95.788, 88.6856, 90.0062,
+    89.5991, 87.6987, 83.2886, 83.6992, 80.0268, 80.2146, 82.2778, 78.2842, 69.7213, 71.6091, 74.349, 61.604, 69.8856,
+    75.087, 63.5927, 46.4182, 66.8054, 63.3828, 64.304, 59.4519, 51.959, 57.4406, 60.3125,
+];
+
+/// The wavelengths one path carries, in nm: a hero wavelength drawn
+/// uniformly over the range and two more a third of the range apart from
+/// it, wrapping around. A color on the path holds the values at these three.
+#[derive(Clone, Copy)]
+pub struct Wavelengths {
+    pub lambda: [f32; 3],
+}
+
+impl Wavelengths {
+    /// Wavelengths for the uniform random number `u`.
+    pub fn sample(u: f32) -> Self {
+        let lambda = std::array::from_fn(|i| {
+            let t = (u + i as f32 / 3.0).fract();
+            LAMBDA_MIN + t * (LAMBDA_MAX - LAMBDA_MIN)
+        });
+        Self { lambda }
+    }
+
+    /// The wavelength that leads the path, which is the one it keeps when
+    /// a dispersive material narrows it down to one.
+    pub fn hero(&self) -> f32 {
+        self.lambda[0]
+    }
+}
+
+/// Conversion of scene colors to spectra and of traced spectra back to
+/// color, all relative to sRGB with its D65 white.
+///
+/// Any color with non-negative components splits into a gray, its smallest
+/// component, and a remainder of some hue with at least one component at
+/// zero. The gray becomes a flat spectrum, and the remainder a multiple of
+/// the spectrum fitted to the fully saturated color of that hue: a sigmoid
+/// of a quadratic in the wavelength (Jakob and Hanika 2019), which stays
+/// within 0 and 1. The result is smooth, scales with the color, never
+/// exceeds the color's largest component, so reflectances stay energy
+/// conserving, and is flat for gray.
+pub struct Spectral {
+    /// Sigmoid coefficients fitted around the hue circle from red through
+    /// yellow, green, cyan, blue and magenta back to red, for a wavelength
+    /// mapped to 0..1 over the traced range.
+    hues: Vec<[f32; 3]>,
+    /// Integral of the CIE y curve, which scales XYZ so that Y is 1 for a
+    /// flat spectrum of 1.
+    y_integral: f32,
+    /// Factor on `D65` that makes its Y 1.
+    d65_scale: f32,
+}
+
+impl Spectral {
+    /// Fits the table of spectra, which takes a few hundred small solves.
+    pub fn new() -> Self {
+        let step = (LAMBDA_MAX - LAMBDA_MIN) / FIT_SAMPLES as f32;
+        let lambdas: Vec<f32> = (0..FIT_SAMPLES).map(|i| LAMBDA_MIN + (i as f32 + 0.5) * step).collect();
+        let y_integral = lambdas.iter().map(|&l| cie_xyz(l).y).sum::<f32>() * step;
+        let d65_y = lambdas.iter().map(|&l| d65(l) * cie_xyz(l).y).sum::<f32>() * step;
+        let d65_scale = y_integral / d65_y;
+
+        // What each wavelength adds to the linear sRGB of a reflectance lit
+        // by D65.
+        let weights: Vec<([f64; 3], f64)> = lambdas
+            .iter()
+            .map(|&l| {
+                let rgb = xyz_to_linear_srgb(cie_xyz(l)) * (d65(l) * d65_scale * step / y_integral);
+                let t = (l - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN);
+                ([rgb.x as f64, rgb.y as f64, rgb.z as f64], t as f64)
+            })
+            .collect();
+
+        // Each fit starts from the one before, so the solver follows the
+        // coefficients smoothly around the circle.
+        let mut coefficients = [0.0; 3];
+        let hues = (0..=6 * HUE_STEPS)
+            .map(|i| {
+                coefficients = fit_sigmoid(hue_color(i as f32 / HUE_STEPS as f32), coefficients, &weights);
+                coefficients.map(|c| c as f32)
+            })
+            .collect();
+
+        Self { hues, y_integral, d65_scale }
+    }
+
+    /// Smooth spectrum with the color `rgb` under D65, at `wavelengths`. It
+    /// is used for reflectances and everything else that scales light.
+    pub fn reflectance(&self, rgb: Color, wavelengths: &Wavelengths) -> Color {
+        let (r, g, b) = (rgb.x.max(0.0), rgb.y.max(0.0), rgb.z.max(0.0));
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let chroma = max - min;
+        if chroma <= 0.0 {
+            return Color::new(min, min, min);
+        }
+
+        let hue = if max == r {
+            ((g - b) / chroma).rem_euclid(6.0)
+        } else if max == g {
+            2.0 + (b - r) / chroma
+        } else {
+            4.0 + (r - g) / chroma
+        };
+        let t = hue * HUE_STEPS as f32;
+        let i = (t as usize).min(6 * HUE_STEPS - 1);
+        let f = t - i as f32;
+        let c: [f32; 3] = std::array::from_fn(|k| self.hues[i][k] * (1.0 - f) + self.hues[i + 1][k] * f);
+
+        let [v0, v1, v2] = wavelengths.lambda.map(|l| min + chroma * sigmoid_spectrum(c, l));
+        Color::new(v0, v1, v2)
+    }
+
+    /// Spectrum of light with the color `rgb`, at `wavelengths`: the
+    /// reflectance of that color lit by D65, so that white light is D65.
+    pub fn illuminant(&self, rgb: Color, wavelengths: &Wavelengths) -> Color {
+        let [d0, d1, d2] = wavelengths.lambda.map(|l| d65(l) * self.d65_scale);
+        let v = self.reflectance(rgb, wavelengths);
+        Color::new(v.x * d0, v.y * d1, v.z * d2)
+    }
+
+    /// Linear sRGB of radiance that has `values` at `wavelengths`, as a
+    /// one-sample estimate over the traced range.
+    pub fn to_rgb(&self, values: Color, wavelengths: &Wavelengths) -> Color {
+        let values = [values.x, values.y, values.z];
+        let mut xyz = Vec3::new(0.0, 0.0, 0.0);
+        for (&lambda, &value) in wavelengths.lambda.iter().zip(&values) {
+            xyz = xyz + cie_xyz(lambda) * value;
+        }
+        // Each wavelength has density 1 / (max - min).
+        xyz_to_linear_srgb(xyz * ((LAMBDA_MAX - LAMBDA_MIN) / (3.0 * self.y_integral)))
+    }
+}
+
+/// The CIE 1931 2° color matching functions at `lambda` in nm, using the
+/// multi-lobe Gaussian fits of Wyman, Sloan and Shirley (2013).
+pub fn cie_xyz(lambda: f32) -> Vec3 {
+    let g = |mu: f32, sigma_below: f32, sigma_above: f32| {
+        let sigma = if lambda < mu { sigma_below } else { sigma_above };
+        let t = (lambda - mu) / sigma;
+        (-0.5 * t * t).exp()
+    };
+    Vec3::new(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+/// Linear sRGB of light of wavelength `lambda` in nm, scaled per channel so
+/// that it averages to white over the traced range. Some components go
+/// negative, as no wavelength on its own is inside the sRGB gamut.
+pub fn wavelength_rgb(lambda: f32) -> Color {
+    static SCALE: OnceLock<Color> = OnceLock::new();
+    let scale = SCALE.get_or_init(|| {
+        let step = (LAMBDA_MAX - LAMBDA_MIN) / FIT_SAMPLES as f32;
+        let mut sum = Color::new(0.0, 0.0, 0.0);
+        for i in 0..FIT_SAMPLES {
+            sum = sum + xyz_to_linear_srgb(cie_xyz(LAMBDA_MIN + (i as f32 + 0.5) * step));
+        }
+        let mean = sum / FIT_SAMPLES as f32;
+        Color::new(1.0 / mean.x, 1.0 / mean.y, 1.0 / mean.z)
+    });
+    let rgb = xyz_to_linear_srgb(cie_xyz(lambda));
+    Color::new(rgb.x * scale.x, rgb.y * scale.y, rgb.z * scale.z)
+}
+
+/// Relative spectral power of D65 at `lambda` in nm.
+fn d65(lambda: f32) -> f32 {
+    let t = ((lambda - LAMBDA_MIN) / 10.0).clamp(0.0, (D65.len() - 1) as f32);
+    let i = (t as usize).min(D65.len() - 2);
+    let f = t - i as f32;
+    D65[i] * (1.0 - f) + D65[i + 1] * f
+}
+
+/// Fully saturated color at `hue`, from 0 to 6 around the circle starting
+/// at red.
+fn hue_color(hue: f32) -> [f64; 3] {
+    let segment = (hue as usize).min(5);
+    let f = (hue - segment as f32) as f64;
+    match segment {
+        0 => [1.0, f, 0.0],
+        1 => [1.0 - f, 1.0, 0.0],
+        2 => [0.0, 1.0, f],
+        3 => [0.0, 1.0 - f, 1.0],
+        4 => [f, 0.0, 1.0],
+        _ => [1.0, 0.0, 1.0 - f],
+    }
+}
+
+/// Maps any real number into (0, 1).
+fn sigmoid(x: f32) -> f32 {
+    0.5 + x / (2.0 * (1.0 + x * x).sqrt())
+}
+
+/// The sigmoid spectrum with coefficients `c` at `lambda` in nm.
+fn sigmoid_spectrum(c: [f32; 3], lambda: f32) -> f32 {
+    let t = (lambda - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN);
+    sigmoid((c[0] * t + c[1]) * t + c[2])
+}
+
+/// Sigmoid coefficients whose spectrum has the color `target`, found by
+/// Newton's method from `start`. `weights` holds what each fitting
+/// wavelength adds to the color, with the wavelength mapped to 0..1. This
+/// runs in double precision, as the coefficients for saturated colors get
+/// large.
+fn fit_sigmoid(target: [f64; 3], start: [f64; 3], weights: &[([f64; 3], f64)]) -> [f64; 3] {
+    let mut c = start;
+    for _ in 0..50 {
+        // The residual and its Jacobian in the coefficients.
+        let mut residual = target.map(|v| -v);
+        let mut jacobian = [[0.0; 3]; 3];
+        for (w, t) in weights {
+            let x = (c[0] * t + c[1]) * t + c[2];
+            let s = 0.5 + x / (2.0 * (1.0 + x * x).sqrt());
+            let ds = 0.5 / (1.0 + x * x).powf(1.5);
+            let dx = [t * t, *t, 1.0];
+            for i in 0..3 {
+                residual[i] += w[i] * s;
+                for j in 0..3 {
+                    jacobian[i][j] += w[i] * ds * dx[j];
+                }
+            }
+        }
+        if residual.iter().map(|r| r * r).sum::<f64>() < 1e-12 {
+            break;
+        }
+        let Some(step) = solve3(jacobian, residual) else {
+            break;
+        };
+        for i in 0..3 {
+            c[i] -= step[i];
+        }
+    }
+    c
+}
+
+/// Solution of the 3×3 system `a x = b` by Cramer's rule, or `None` when
+/// `a` is singular.
+fn solve3(a: [[f64; 3]; 3], b: [f64; 3]) -> Option<[f64; 3]> {
+    let det = |m: [[f64; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1]) - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+    let d = det(a);
+    if d.abs() < 1e-300 {
+        return None;
+    }
+    Some(std::array::from_fn(|k| {
+        let mut m = a;
+        for i in 0..3 {
+            m[i][k] = b[i];
+        }
+        det(m) / d
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Linear sRGB of light with the color `rgb` traced spectrally,
+    /// integrated over evenly spread hero wavelengths.
+    fn round_trip(spectral: &Spectral, rgb: Color) -> Color {
+        let n = 600;
+        let mut sum = Color::new(0.0, 0.0, 0.0);
+        for i in 0..n {
+            let wavelengths = Wavelengths::sample((i as f32 + 0.5) / n as f32);
+            sum = sum + spectral.to_rgb(spectral.illuminant(rgb, &wavelengths), &wavelengths);
+        }
+        sum / n as f32
+    }
+
+    #[test]
+    fn colors_survive_the_trip_through_spectra() {
+        let spectral = Spectral::new();
+        for rgb in [[1.0, 1.0, 1.0], [0.8, 0.2, 0.1], [0.1, 0.5, 0.9], [0.3, 0.9, 0.3], [0.6, 0.1, 0.7], [0.05, 0.05, 0.02]] {
+            let back = round_trip(&spectral, Color::new(rgb[0], rgb[1], rgb[2]));
+            for (channel, expected) in [back.x, back.y, back.z].into_iter().zip(rgb) {
+                assert!((channel - expected).abs() < 0.02, "{:?} came back as {:?}", rgb, [back.x, back.y, back.z]);
+            }
+        }
+    }
+
+    #[test]
+    fn reflectances_stay_within_the_color() {
+        let spectral = Spectral::new();
+        let gray = spectral.reflectance(Color::new(0.4, 0.4, 0.4), &Wavelengths::sample(0.3));
+        assert_eq!([gray.x, gray.y, gray.z], [0.4, 0.4, 0.4]);
+        for i in 0..100 {
+            let wavelengths = Wavelengths::sample(i as f32 / 100.0);
+            for rgb in [Color::new(0.9, 0.1, 0.0), Color::new(0.0, 0.7, 1.0), Color::new(0.5, 0.2, 0.4)] {
+                let v = spectral.reflectance(rgb, &wavelengths);
+                let (min, max) = (rgb.x.min(rgb.y).min(rgb.z), rgb.x.max(rgb.y).max(rgb.z));
+                assert!([v.x, v.y, v.z].iter().all(|&s| s >= min && s <= max));
+            }
+        }
+    }
+
+    #[test]
+    fn fitted_sigmoids_reach_their_targets() {
+        let step = (LAMBDA_MAX - LAMBDA_MIN) as f64 / FIT_SAMPLES as f64;
+        let weights: Vec<([f64; 3], f64)> = (0..FIT_SAMPLES)
+            .map(|i| {
+                let t = (i as f64 + 0.5) / FIT_SAMPLES as f64;
+                ([t * step, (1.0 - t) * step, 4.0 * t * (1.0 - t) * step], t)
+            })
+            .collect();
+        // What the weights give for a spectrum of 1.
+        let full: [f64; 3] = std::array::from_fn(|i| weights.iter().map(|(w, _)| w[i]).sum());
+        let target = [0.3 * full[0], 0.6 * full[1], 0.5 * full[2]];
+        let c = fit_sigmoid(target, [0.0; 3], &weights);
+        for (i, &expected) in target.iter().enumerate() {
+            let actual: f64 = weights.iter().map(|(w, t)| w[i] * sigmoid_spectrum(c.map(|c| c as f32), LAMBDA_MIN + *t as f32 * (LAMBDA_MAX - LAMBDA_MIN)) as f64).sum();
+            assert!((actual - expected).abs() < 1e-3 * full[i], "channel {}: {} vs {}", i, actual, expected);
+        }
+    }
+}
@@ -1,15 +1,25 @@
-use std::ops::Mul;
-
-use crate::material::{self, Material, Scatter};
-use crate::vec3::{div, LengthSquared, mul, Dot, Vec3};
+use crate::aabb::{Aabb, BoundingBox};
+use crate::bvh::Bvh;
+use crate::instance::Instance;
+use crate::medium::ConstantMedium;
+use crate::light::SampleLight;
+use crate::material::{self, Material};
+use crate::mesh::MeshTriangle;
+use crate::plane::{Disk, Plane};
+use crate::quad::Quad;
+use crate::rtweekend::{random_double, INF, PI};
+use crate::texture::{SolidColor, Texture};
+use crate::triangle::Triangle;
+use crate::vec3::{random_unit_vector, LengthSquared, Dot, Onb, Vec3};
 use crate::point3::Point3;
-use crate::ray::{At, Direction, Origin, Ray};
+use crate::ray::{At, Direction, Origin, Ray, Time};
 use crate::interval::{Interval, Surrounds};
 
 pub trait Hit {
-    fn hit(&self, r: Ray, ray_t: Interval, rec: &mut HitRecord) -> bool;
+    fn hit<'a>(&'a self, r: Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool;
 }
 
+#[allow(dead_code)]
 pub trait Clear {
     fn clear(&mut self);
 }
@@ -17,16 +27,74 @@ pub trait Clear {
 #[derive(Clone)]
 pub enum HittableObject {
     Sphere(Sphere),
+    MovingSphere(MovingSphere),
+    Triangle(Triangle),
+    MeshTriangle(MeshTriangle),
+    Quad(Quad),
+    Disk(Disk),
+    Plane(Plane),
+    Bvh(Bvh),
+    Instance(Instance),
+    ConstantMedium(ConstantMedium),
 }
 impl Hit for HittableObject {
-    fn hit(&self, r: Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
+    fn hit<'a>(&'a self, r: Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
         match self {
             HittableObject::Sphere(sphere) => sphere.hit(r, ray_t, rec),
+            HittableObject::MovingSphere(sphere) => sphere.hit(r, ray_t, rec),
+            HittableObject::Triangle(triangle) => triangle.hit(r, ray_t, rec),
+            HittableObject::MeshTriangle(triangle) => triangle.hit(r, ray_t, rec),
+            HittableObject::Quad(quad) => quad.hit(r, ray_t, rec),
+            HittableObject::Disk(disk) => disk.hit(r, ray_t, rec),
+            HittableObject::Plane(plane) => plane.hit(r, ray_t, rec),
+            HittableObject::Bvh(bvh) => bvh.hit(r, ray_t, rec),
+            HittableObject::Instance(instance) => instance.hit(r, ray_t, rec),
+            HittableObject::ConstantMedium(medium) => medium.hit(r, ray_t, rec),
             // Add other cases here as needed
         }
     }
 }
 
+impl BoundingBox for HittableObject {
+    fn bounding_box(&self) -> Aabb {
+        match self {
+            HittableObject::Sphere(sphere) => sphere.bounding_box(),
+            HittableObject::MovingSphere(sphere) => sphere.bounding_box(),
+            HittableObject::Triangle(triangle) => triangle.bounding_box(),
+            HittableObject::MeshTriangle(triangle) => triangle.bounding_box(),
+            HittableObject::Quad(quad) => quad.bounding_box(),
+            HittableObject::Disk(disk) => disk.bounding_box(),
+            HittableObject::Plane(plane) => plane.bounding_box(),
+            HittableObject::Bvh(bvh) => bvh.bounding_box(),
+            HittableObject::Instance(instance) => instance.bounding_box(),
+            HittableObject::ConstantMedium(medium) => medium.bounding_box(),
+        }
+    }
+}
+
+impl SampleLight for HittableObject {
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f32 {
+        match self {
+            HittableObject::Sphere(sphere) => sphere.pdf_value(origin, direction),
+            HittableObject::Triangle(triangle) => triangle.pdf_value(origin, direction),
+            HittableObject::Quad(quad) => quad.pdf_value(origin, direction),
+            HittableObject::Disk(disk) => disk.pdf_value(origin, direction),
+            // Only simple shapes can be sampled as lights for now.
+            _ => 0.0,
+        }
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        match self {
+            HittableObject::Sphere(sphere) => sphere.random(origin),
+            HittableObject::Triangle(triangle) => triangle.random(origin),
+            HittableObject::Quad(quad) => quad.random(origin),
+            HittableObject::Disk(disk) => disk.random(origin),
+            _ => Vec3::new(1.0, 0.0, 0.0),
+        }
+    }
+}
+
 pub trait New {
     fn new() -> Self;
 }
@@ -38,12 +106,15 @@ pub trait Add {
     fn add(&mut self, rhs: HittableObject);
 }
 
-pub struct HitRecord {
+pub struct HitRecord<'a> {
     pub(crate) p: Point3,
     pub(crate) normal: Vec3,
     pub(crate) t: f32,
+    /// Surface coordinates of the hit point, in the primitive's own parameterization.
+    pub(crate) u: f32,
+    pub(crate) v: f32,
     pub(crate) front_face: bool,
-    pub(crate) mat: Material,
+    pub(crate) mat: &'a Material,
 }
 pub struct Sphere {
     pub(crate) center: Point3,
@@ -51,54 +122,152 @@ pub struct Sphere {
     pub(crate) mat: Material,
 }
 
+/// Sphere whose center moves linearly from `center0` at `time0` to `center1`
+/// at `time1`. Outside that interval it stays at the nearer end point, so
+/// its bounding box covers every position it can be hit at.
+#[derive(Clone)]
+pub struct MovingSphere {
+    pub(crate) center0: Point3,
+    pub(crate) center1: Point3,
+    pub(crate) time0: f32,
+    pub(crate) time1: f32,
+    pub(crate) radius: f32,
+    pub(crate) mat: Material,
+}
+
 pub struct HittableList {
-    objects: Vec<HittableObject>,
+    pub(crate) objects: Vec<HittableObject>,
 }
 
 
 impl Hit for Sphere {
-    fn hit(&self, r: Ray, ray_t: Interval, rec: &mut HitRecord) -> bool {
-        let oc = self.center - r.origin();
-        let a = r.direction().length_squared();
-        let h = oc.dot(r.direction());
-        let c = oc.length_squared() - self.radius*self.radius;
-        let discriminant = h*h - a*c;
-
-        if discriminant < 0.0 {
-            return false;
-        } else {
-            let root = discriminant.sqrt();
-            let mut temp = (h - root) / a;
+    fn hit<'a>(&'a self, r: Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        hit_sphere(self.center, self.radius, &self.mat, r, ray_t, rec)
+    }
+}
+
+impl Hit for MovingSphere {
+    fn hit<'a>(&'a self, r: Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        hit_sphere(self.center(r.time()), self.radius, &self.mat, r, ray_t, rec)
+    }
+}
+
+fn hit_sphere<'a>(center: Point3, radius: f32, mat: &'a Material, r: Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+    let oc = center - r.origin();
+    let a = r.direction().length_squared();
+    let h = oc.dot(r.direction());
+    let c = oc.length_squared() - radius*radius;
+    let discriminant = h*h - a*c;
+
+    if discriminant < 0.0 {
+        false
+    } else {
+        let root = discriminant.sqrt();
+        let mut temp = (h - root) / a;
+        if !ray_t.surrounds(temp) {
+            temp = (h + root) / a;
             if !ray_t.surrounds(temp) {
-                temp = (h + root) / a;
-                if !ray_t.surrounds(temp) {
-                    return false;
-                }
+                return false;
             }
-            rec.t = temp;
-            rec.p = r.at(rec.t);
-            
-            let outward_normal = (rec.p - self.center) / self.radius;
+        }
+        rec.t = temp;
+        rec.p = r.at(rec.t);
+        
+        let outward_normal = (rec.p - center) / radius;
+
+        rec.set_face_normal(r, outward_normal);
+        (rec.u, rec.v) = get_sphere_uv(outward_normal);
 
-            rec.set_face_normal(r, outward_normal);
+        rec.mat = mat;
 
-            rec.mat = self.mat;
+        true
+    }
+}
+
+/// Maps a point on the unit sphere to (u, v) in [0, 1]: u is the angle
+/// around the y axis starting from -x, v the angle from -y up to +y.
+fn get_sphere_uv(p: Point3) -> (f32, f32) {
+    let theta = (-p.y).acos();
+    let phi = (-p.z).atan2(p.x) + PI;
+    (phi / (2.0 * PI), theta / PI)
+}
+
+impl SampleLight for Sphere {
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f32 {
+        let mut rec = HitRecord::new();
+        if !self.hit(Ray::new(origin, direction), Interval::new(0.001, INF), &mut rec) {
+            return 0.0;
+        }
+
+        let distance_squared = (self.center - origin).length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return 1.0 / (4.0 * PI);
+        }
+        let cos_theta_max = (1.0 - self.radius * self.radius / distance_squared).sqrt();
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
 
-            return true;
+        1.0 / solid_angle
+    }
+
+    /// Samples uniformly within the cone of directions the sphere subtends,
+    /// or over all directions when `origin` is inside it.
+    fn random(&self, origin: Point3) -> Vec3 {
+        let direction = self.center - origin;
+        let distance_squared = direction.length_squared();
+        if distance_squared <= self.radius * self.radius {
+            return random_unit_vector();
         }
+
+        let r1 = random_double();
+        let r2 = random_double();
+        let cos_theta_max = (1.0 - self.radius * self.radius / distance_squared).sqrt();
+        let z = 1.0 + r2 * (cos_theta_max - 1.0);
+        let phi = 2.0 * PI * r1;
+        let sin_theta = (1.0 - z * z).sqrt();
+
+        let uvw = Onb::new(direction);
+        uvw.transform(Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, z))
     }
 }
 
-impl SetFaceNormal for HitRecord {
+impl MovingSphere {
+    pub fn new(center0: Point3, center1: Point3, time0: f32, time1: f32, radius: f32, mat: Material) -> Self {
+        Self { center0, center1, time0, time1, radius, mat }
+    }
+
+    pub fn center(&self, time: f32) -> Point3 {
+        let span = self.time1 - self.time0;
+        let s = if span > 0.0 { ((time - self.time0) / span).clamp(0.0, 1.0) } else { 0.0 };
+        self.center0 + (self.center1 - self.center0) * s
+    }
+}
+
+impl BoundingBox for MovingSphere {
+    fn bounding_box(&self) -> Aabb {
+        let rvec = Vec3::new(self.radius, self.radius, self.radius);
+        let box0 = Aabb::from_points(self.center0 - rvec, self.center0 + rvec);
+        let box1 = Aabb::from_points(self.center1 - rvec, self.center1 + rvec);
+        Aabb::enclosing(box0, box1)
+    }
+}
+
+impl BoundingBox for Sphere {
+    fn bounding_box(&self) -> Aabb {
+        let rvec = Vec3::new(self.radius, self.radius, self.radius);
+        Aabb::from_points(self.center - rvec, self.center + rvec)
+    }
+}
+
+impl SetFaceNormal for HitRecord<'_> {
     fn set_face_normal(&mut self, r: Ray, outward_normal: Vec3) {
         self.front_face = r.direction().dot(outward_normal) < 0.0;
         self.normal = if self.front_face {outward_normal} else {-outward_normal};
     }
 }
 
-impl Copy for HitRecord {}
+impl Copy for HitRecord<'_> {}
 
-impl Clone for HitRecord {
+impl Clone for HitRecord<'_> {
     fn clone(&self) -> Self {
         *self
     }
@@ -117,14 +286,8 @@ impl Clear for HittableList {
 }
 
 impl Hit for HittableList {
-    fn hit(&self, r: Ray, ray_t: Interval,  rec: &mut HitRecord) -> bool {
-        let mut temp_rec = HitRecord {
-            p: Point3 { x: 0.0, y: 0.0, z: 0.0 },
-            normal: Vec3 { x: 0.0, y: 0.0, z: 0.0 },
-            t: 0.0,
-            mat: material::Material::Lambertian(material::Lambertian::new(Vec3::new(0.0, 0.0, 0.0))),
-            front_face: false,
-        };
+    fn hit<'a>(&'a self, r: Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        let mut temp_rec = HitRecord::new();
         let mut hit_anything = false;
         let mut closest_so_far = ray_t.max;
 
@@ -132,11 +295,7 @@ impl Hit for HittableList {
             if object.hit(r,  Interval::new(ray_t.min, closest_so_far), &mut temp_rec) {
                 hit_anything = true;
                 closest_so_far = temp_rec.t;
-                rec.t = temp_rec.t;
-                rec.p = temp_rec.p;
-                rec.normal = temp_rec.normal;
-                rec.front_face = temp_rec.front_face;
-                rec.mat = temp_rec.mat;
+                *rec = temp_rec;
             }
         }
 
@@ -175,21 +334,35 @@ impl Add for HittableList {
     }
 }
 
+impl BoundingBox for HittableList {
+    fn bounding_box(&self) -> Aabb {
+        self.objects.iter().fold(Aabb::EMPTY, |acc, object| Aabb::enclosing(acc, object.bounding_box()))
+    }
+}
+
 impl Sphere {
+    #[allow(dead_code)]
     pub fn new(center: Point3, radius: f32) -> Self {
         
         Self { center, radius, mat: material::Material::Lambertian(material::Lambertian::new(Vec3::new(0.0, 0.0, 0.0))) }
     }
 }
 
-impl New for HitRecord {
+// Placeholder for records that haven't been filled in by a hit yet.
+static NO_MATERIAL: Material = Material::Lambertian(material::Lambertian {
+    albedo: Texture::Solid(SolidColor { albedo: Vec3 { x: 0.0, y: 0.0, z: 0.0 } }),
+});
+
+impl New for HitRecord<'_> {
     fn new() -> Self {
         HitRecord {
             p: Point3 { x: 0.0, y: 0.0, z: 0.0 },
             normal: Vec3 { x: 0.0, y: 0.0, z: 0.0 },
             t: 0.0,
+            u: 0.0,
+            v: 0.0,
             front_face: false,
-            mat: material::Material::Lambertian(material::Lambertian::new(Vec3::new(0.0, 0.0, 0.0))),
+            mat: &NO_MATERIAL,
         }
     }
 }
@@ -0,0 +1,160 @@
+use std::sync::Arc;
+
+use crate::color::Color;
+use crate::image::Image;
+use crate::interval::{Clamp, Interval};
+use crate::perlin::Perlin;
+use crate::point3::Point3;
+
+/// Evaluates a texture at surface coordinates `(u, v)` and world point `p`.
+pub trait Value {
+    fn value(&self, u: f32, v: f32, p: Point3) -> Color;
+}
+
+#[derive(Clone)]
+pub enum Texture {
+    Solid(SolidColor),
+    Checker(CheckerTexture),
+    Image(ImageTexture),
+    Noise(NoiseTexture),
+    // Add other texture types here as needed
+}
+
+#[derive(Clone)]
+pub struct SolidColor {
+    pub albedo: Color,
+}
+
+/// Alternates between two textures on a 3D lattice of cubes with side `scale`.
+#[derive(Clone)]
+pub struct CheckerTexture {
+    inv_scale: f32,
+    even: Arc<Texture>,
+    odd: Arc<Texture>,
+}
+
+/// Image mapped onto the surface's `(u, v)`, repeating outside [0, 1] and
+/// filtered bilinearly.
+#[derive(Clone)]
+pub struct ImageTexture {
+    image: Arc<Image>,
+}
+
+#[derive(Clone, Copy)]
+pub enum NoiseStyle {
+    /// Plain Perlin noise.
+    Smooth,
+    /// Sum of several noise octaves.
+    Turbulence,
+    /// Sine stripes along z, phase-shifted by turbulence.
+    Marble,
+}
+
+#[derive(Clone)]
+pub struct NoiseTexture {
+    noise: Arc<Perlin>,
+    scale: f32,
+    style: NoiseStyle,
+}
+
+impl Value for Texture {
+    fn value(&self, u: f32, v: f32, p: Point3) -> Color {
+        match self {
+            Texture::Solid(solid) => solid.value(u, v, p),
+            Texture::Checker(checker) => checker.value(u, v, p),
+            Texture::Image(image) => image.value(u, v, p),
+            Texture::Noise(noise) => noise.value(u, v, p),
+        }
+    }
+}
+
+impl Texture {
+    pub fn solid(albedo: Color) -> Self {
+        Texture::Solid(SolidColor { albedo })
+    }
+}
+
+impl From<Color> for Texture {
+    fn from(albedo: Color) -> Self {
+        Texture::solid(albedo)
+    }
+}
+
+impl Value for SolidColor {
+    fn value(&self, _u: f32, _v: f32, _p: Point3) -> Color {
+        self.albedo
+    }
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f32, even: Texture, odd: Texture) -> Self {
+        Self { inv_scale: 1.0 / scale, even: Arc::new(even), odd: Arc::new(odd) }
+    }
+}
+
+impl Value for CheckerTexture {
+    fn value(&self, u: f32, v: f32, p: Point3) -> Color {
+        let x = (self.inv_scale * p.x).floor() as i32;
+        let y = (self.inv_scale * p.y).floor() as i32;
+        let z = (self.inv_scale * p.z).floor() as i32;
+
+        if (x + y + z) % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+impl ImageTexture {
+    pub fn new(image: Image) -> Self {
+        Self { image: Arc::new(image) }
+    }
+}
+
+impl Value for ImageTexture {
+    fn value(&self, u: f32, v: f32, _p: Point3) -> Color {
+        let image = &self.image;
+        if image.width == 0 || image.height == 0 {
+            return Color::new(0.0, 1.0, 1.0);
+        }
+
+        // Wrap to [0, 1) and flip v, since images are stored top row first.
+        let u = u - u.floor();
+        let v = 1.0 - (v - v.floor());
+
+        // Continuous pixel coordinates, with pixel centers at half-integers.
+        let x = u * image.width as f32 - 0.5;
+        let y = Interval::new(0.0, image.height as f32 - 1.0).clamp(v * image.height as f32 - 0.5);
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let tx = x - x0;
+        let ty = y - y0;
+
+        let wrap_x = |x: f32| (x as i64).rem_euclid(image.width as i64) as usize;
+        let clamp_y = |y: f32| (y.max(0.0) as usize).min(image.height - 1);
+        let (xa, xb) = (wrap_x(x0), wrap_x(x0 + 1.0));
+        let (ya, yb) = (clamp_y(y0), clamp_y(y0 + 1.0));
+
+        let top = image.pixel(xa, ya) * (1.0 - tx) + image.pixel(xb, ya) * tx;
+        let bottom = image.pixel(xa, yb) * (1.0 - tx) + image.pixel(xb, yb) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+}
+
+impl NoiseTexture {
+    pub fn new(scale: f32, style: NoiseStyle) -> Self {
+        Self { noise: Arc::new(Perlin::new()), scale, style }
+    }
+}
+
+impl Value for NoiseTexture {
+    fn value(&self, _u: f32, _v: f32, p: Point3) -> Color {
+        let gray = Color::new(1.0, 1.0, 1.0);
+        match self.style {
+            NoiseStyle::Smooth => gray * 0.5 * (1.0 + self.noise.noise(p * self.scale)),
+            NoiseStyle::Turbulence => gray * self.noise.turb(p * self.scale, 7),
+            NoiseStyle::Marble => gray * 0.5 * (1.0 + (self.scale * p.z + 10.0 * self.noise.turb(p, 7)).sin()),
+        }
+    }
+}
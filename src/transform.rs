@@ -0,0 +1,156 @@
+use crate::aabb::Aabb;
+use crate::interval::Interval;
+use crate::point3::Point3;
+use crate::rtweekend::degrees_to_radians;
+use crate::vec3::{Unit, Vec3};
+
+/// Rows of a 3x4 affine matrix: the 3x3 linear part followed by the translation.
+type Matrix = [[f32; 4]; 3];
+
+const IDENTITY: Matrix = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+];
+
+/// Affine transform from object space to world space. The inverse is built
+/// alongside the forward matrix rather than computed from it, so it stays
+/// exact for every rotation, scale and translation.
+pub struct Transform {
+    m: Matrix,
+    inv: Matrix,
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform { m: IDENTITY, inv: IDENTITY };
+
+    pub fn translate(offset: Vec3) -> Self {
+        let mut m = IDENTITY;
+        let mut inv = IDENTITY;
+        for i in 0..3 {
+            m[i][3] = offset[i];
+            inv[i][3] = -offset[i];
+        }
+        Self { m, inv }
+    }
+
+    /// Scales along each axis. Every factor must be non-zero.
+    pub fn scale(factors: Vec3) -> Self {
+        let mut m = IDENTITY;
+        let mut inv = IDENTITY;
+        for i in 0..3 {
+            m[i][i] = factors[i];
+            inv[i][i] = 1.0 / factors[i];
+        }
+        Self { m, inv }
+    }
+
+    /// Rotates counter-clockwise by `degrees` about `axis`, looking down the
+    /// axis towards the origin.
+    pub fn rotate(axis: Vec3, degrees: f32) -> Self {
+        let a = axis.unit();
+        let theta = degrees_to_radians(degrees);
+        let (sin, cos) = theta.sin_cos();
+        let k = 1.0 - cos;
+
+        // Rodrigues' rotation formula.
+        let m = [
+            [cos + a.x * a.x * k, a.x * a.y * k - a.z * sin, a.x * a.z * k + a.y * sin, 0.0],
+            [a.y * a.x * k + a.z * sin, cos + a.y * a.y * k, a.y * a.z * k - a.x * sin, 0.0],
+            [a.z * a.x * k - a.y * sin, a.z * a.y * k + a.x * sin, cos + a.z * a.z * k, 0.0],
+        ];
+        // A rotation's inverse is its transpose.
+        let mut inv = IDENTITY;
+        for (i, row) in inv.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().take(3).enumerate() {
+                *value = m[j][i];
+            }
+        }
+        Self { m, inv }
+    }
+
+    /// The transform that applies `self` first and then `next`.
+    pub fn then(&self, next: &Transform) -> Self {
+        Self { m: compose(&next.m, &self.m), inv: compose(&self.inv, &next.inv) }
+    }
+
+    pub fn inverse(&self) -> Self {
+        Self { m: self.inv, inv: self.m }
+    }
+
+    pub fn point(&self, p: Point3) -> Point3 {
+        apply(&self.m, p, 1.0)
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        apply(&self.m, v, 0.0)
+    }
+
+    /// Transforms a surface normal with the inverse transpose, so it stays
+    /// perpendicular to the transformed surface. The result isn't normalized.
+    pub fn normal(&self, n: Vec3) -> Vec3 {
+        let inv = &self.inv;
+        Vec3::new(
+            inv[0][0] * n.x + inv[1][0] * n.y + inv[2][0] * n.z,
+            inv[0][1] * n.x + inv[1][1] * n.y + inv[2][1] * n.z,
+            inv[0][2] * n.x + inv[1][2] * n.y + inv[2][2] * n.z,
+        )
+    }
+
+    /// Smallest axis-aligned box around `bbox` after transforming it.
+    pub fn bounding_box(&self, bbox: Aabb) -> Aabb {
+        if !bbox.is_bounded() {
+            return Aabb::UNIVERSE;
+        }
+        let mut min = [f32::INFINITY; 3];
+        let mut max = [f32::NEG_INFINITY; 3];
+        for corner in 0..8 {
+            let p = Point3::new(
+                if corner & 1 == 0 { bbox.x.min } else { bbox.x.max },
+                if corner & 2 == 0 { bbox.y.min } else { bbox.y.max },
+                if corner & 4 == 0 { bbox.z.min } else { bbox.z.max },
+            );
+            let q = self.point(p);
+            for axis in 0..3 {
+                min[axis] = min[axis].min(q[axis]);
+                max[axis] = max[axis].max(q[axis]);
+            }
+        }
+        Aabb::new(
+            Interval::new(min[0], max[0]),
+            Interval::new(min[1], max[1]),
+            Interval::new(min[2], max[2]),
+        )
+    }
+}
+
+fn apply(m: &Matrix, v: Vec3, w: f32) -> Vec3 {
+    Vec3::new(
+        m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z + m[0][3] * w,
+        m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z + m[1][3] * w,
+        m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z + m[2][3] * w,
+    )
+}
+
+/// The matrix product `a * b`, treating both as 4x4 with an implicit last row
+/// of (0, 0, 0, 1).
+fn compose(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut out = [[0.0; 4]; 3];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+            if j == 3 {
+                *value += a[i][3];
+            }
+        }
+    }
+    out
+}
+
+impl Copy for Transform {}
+
+impl Clone for Transform {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
@@ -0,0 +1,124 @@
+use crate::aabb::{Aabb, BoundingBox};
+use crate::interval::{Interval, Surrounds};
+use crate::light::SampleLight;
+use crate::material::Material;
+use crate::point3::Point3;
+use crate::ray::{At, Direction, Origin, Ray};
+use crate::rtweekend::{random_double, INF};
+use crate::sphere::{Hit, HitRecord, New, SetFaceNormal};
+use crate::vec3::{cross, Dot, Length, LengthSquared, Unit, Vec3};
+
+// Rays closer than this to parallel with the triangle's plane are treated as misses.
+const PARALLEL_EPSILON: f32 = 1e-8;
+
+/// A single triangle with its own vertices. Large meshes should use
+/// `mesh::Mesh`, which shares vertex data between triangles.
+pub struct Triangle {
+    pub(crate) v0: Point3,
+    pub(crate) v1: Point3,
+    pub(crate) v2: Point3,
+    pub(crate) mat: Material,
+}
+
+/// Barycentric coordinates and ray parameter of a ray-triangle intersection.
+pub struct TriangleHit {
+    pub t: f32,
+    pub b1: f32,
+    pub b2: f32,
+}
+
+/// Möller–Trumbore ray-triangle intersection. On a hit, the point is
+/// `v0 * (1 - b1 - b2) + v1 * b1 + v2 * b2`.
+pub fn intersect(r: &Ray, v0: Point3, v1: Point3, v2: Point3, ray_t: Interval) -> Option<TriangleHit> {
+    let edge1 = v1 - v0;
+    let edge2 = v2 - v0;
+    let pvec = cross(r.direction(), edge2);
+    let det = edge1.dot(pvec);
+    if det.abs() < PARALLEL_EPSILON {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let tvec = r.origin() - v0;
+    let b1 = tvec.dot(pvec) * inv_det;
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let qvec = cross(tvec, edge1);
+    let b2 = r.direction().dot(qvec) * inv_det;
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(qvec) * inv_det;
+    if !ray_t.surrounds(t) {
+        return None;
+    }
+
+    Some(TriangleHit { t, b1, b2 })
+}
+
+impl Triangle {
+    pub fn new(v0: Point3, v1: Point3, v2: Point3, mat: Material) -> Self {
+        Self { v0, v1, v2, mat }
+    }
+}
+
+impl Hit for Triangle {
+    fn hit<'a>(&'a self, r: Ray, ray_t: Interval, rec: &mut HitRecord<'a>) -> bool {
+        let Some(hit) = intersect(&r, self.v0, self.v1, self.v2, ray_t) else {
+            return false;
+        };
+
+        rec.t = hit.t;
+        rec.p = r.at(hit.t);
+        rec.u = hit.b1;
+        rec.v = hit.b2;
+        let outward_normal = cross(self.v1 - self.v0, self.v2 - self.v0).unit();
+        rec.set_face_normal(r, outward_normal);
+        rec.mat = &self.mat;
+        true
+    }
+}
+
+impl BoundingBox for Triangle {
+    fn bounding_box(&self) -> Aabb {
+        Aabb::enclosing(Aabb::from_points(self.v0, self.v1), Aabb::from_points(self.v0, self.v2))
+    }
+}
+
+impl SampleLight for Triangle {
+    fn pdf_value(&self, origin: Point3, direction: Vec3) -> f32 {
+        let mut rec = HitRecord::new();
+        if !self.hit(Ray::new(origin, direction), Interval::new(0.001, INF), &mut rec) {
+            return 0.0;
+        }
+
+        let area = 0.5 * cross(self.v1 - self.v0, self.v2 - self.v0).length();
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = (direction.dot(rec.normal) / direction.length()).abs();
+
+        distance_squared / (cosine * area)
+    }
+
+    fn random(&self, origin: Point3) -> Vec3 {
+        // Uniform over the triangle's area.
+        let su = random_double().sqrt();
+        let b1 = su * (1.0 - random_double());
+        let b2 = su - b1;
+        let p = self.v0 * (1.0 - su) + self.v1 * b1 + self.v2 * b2;
+        p - origin
+    }
+}
+
+impl Clone for Triangle {
+    fn clone(&self) -> Self {
+        Triangle {
+            v0: self.v0,
+            v1: self.v1,
+            v2: self.v2,
+            mat: self.mat.clone(),
+        }
+    }
+}
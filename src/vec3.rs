@@ -1,12 +1,13 @@
-use std::cmp::min;
 use std::ops;
 use std::fmt;
 use std::ops::Div;
+use std::ops::Index;
 use std::ops::Mul;
 use std::ops::Neg;
 
 use crate::rtweekend::random_double;
 use crate::rtweekend::random_double_range;
+use crate::rtweekend::PI;
 
 pub trait NearZero {
     fn near_zero(&self) -> bool;
@@ -24,6 +25,7 @@ pub trait Dot {
     fn dot(&self, rhs: Self) -> f32;
 }
 
+#[allow(dead_code)]
 pub trait Cross {
     fn cross(&self, rhs: Self) -> Self;
 } 
@@ -32,11 +34,13 @@ pub trait Unit {
     fn unit(&self) -> Self;
 }
 
+#[allow(dead_code)]
 pub trait Division {
     type Output;
     fn div(self, rhs: f32) -> Self::Output;
 }
 
+#[allow(dead_code)]
 pub trait Multiplication {
     type Output;
     fn mul(self, rhs: f32) -> Self::Output;
@@ -155,6 +159,7 @@ pub fn cross (v1: Vec3, v2: Vec3) -> Vec3 {
     }
 }
 
+#[allow(dead_code)]
 pub fn mul (v: Vec3, t: f32) -> Vec3 {
     Vec3 {
         x: v.x * t,
@@ -163,6 +168,7 @@ pub fn mul (v: Vec3, t: f32) -> Vec3 {
     }
 }
 
+#[allow(dead_code)]
 pub fn div (v: Vec3, t: f32) -> Vec3 {
     Vec3 {
         x: v.x / t,
@@ -189,6 +195,19 @@ impl Vec3 {
     }
 }
 
+impl Index<usize> for Vec3 {
+    type Output = f32;
+
+    fn index(&self, i: usize) -> &f32 {
+        match i {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 index out of range: {}", i),
+        }
+    }
+}
+
 impl Mul<f32> for Vec3 {
     type Output = Self;
 
@@ -234,20 +253,33 @@ pub fn random_unit_vector() -> Vec3 {
     random_in_unit_sphere().unit()
 }
 
+/// Random direction on the hemisphere around +z, with density cos(theta) / pi.
+pub fn random_cosine_direction() -> Vec3 {
+    let r1 = random_double();
+    let r2 = random_double();
+
+    let phi = 2.0 * PI * r1;
+    let x = phi.cos() * r2.sqrt();
+    let y = phi.sin() * r2.sqrt();
+    let z = (1.0 - r2).sqrt();
+    Vec3::new(x, y, z)
+}
+
+#[allow(dead_code)]
 pub fn random_on_hemisphere(normal: &Vec3) -> Vec3 {
     let on_unit_sphere = random_unit_vector();
     if dot(on_unit_sphere, *normal) > 0.0 {
-        return on_unit_sphere
+        on_unit_sphere
     }
     else {
-        return - on_unit_sphere
+        - on_unit_sphere
     }
 }
 
 impl NearZero for Vec3 {
     fn near_zero(&self) -> bool {
         let s = 1e-8;
-        return self.x.abs() < s && self.y.abs() < s && self.z.abs() < s
+        self.x.abs() < s && self.y.abs() < s && self.z.abs() < s
     }
 }
 
@@ -278,3 +310,40 @@ pub fn random_in_unit_disk() -> Vec3 {
         }
     }
 }
+
+/// Orthonormal basis whose `w` axis points along a given direction, for
+/// sampling in a local frame and mapping the result back to world space.
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    pub fn new(n: Vec3) -> Self {
+        let w = n.unit();
+        let a = if w.x.abs() > 0.9 { Vec3::new(0.0, 1.0, 0.0) } else { Vec3::new(1.0, 0.0, 0.0) };
+        let v = cross(w, a).unit();
+        let u = cross(w, v);
+        Self { u, v, w }
+    }
+
+    /// Maps coordinates in this basis to world space.
+    pub fn transform(&self, v: Vec3) -> Vec3 {
+        self.u * v.x + self.v * v.y + self.w * v.z
+    }
+
+    /// Maps a world-space vector to coordinates in this basis; the inverse
+    /// of `transform`.
+    pub fn local(&self, v: Vec3) -> Vec3 {
+        Vec3::new(v.dot(self.u), v.dot(self.v), v.dot(self.w))
+    }
+}
+
+impl Copy for Onb {}
+
+impl Clone for Onb {
+    fn clone(&self) -> Self {
+        *self
+    }
+}